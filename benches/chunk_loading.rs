@@ -1,42 +1,21 @@
-use bevy::{
-    prelude::*,
-    render::{
-        mesh::{Indices, VertexAttributeValues},
-        pipeline::PrimitiveTopology::TriangleList,
-    },
-};
+use bevy::prelude::*;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use dashmap::DashMap;
-use std::sync::Arc;
-
-#[path = "../src/chunk.rs"]
-mod chunk;
-use chunk::Chunk;
+use steve::{preset::WorldPreset, world::World};
 
 pub fn criterion_benchmark(c: &mut Criterion) {
-    let chunks = Arc::new(DashMap::new());
-
+    let world = World::new(WorldPreset::Default, 1, false);
     c.bench_function("Full Chunk Generation", |b| {
-        b.iter(|| chunk_task(black_box(0), chunks.clone()))
+        b.iter(|| world.generate(black_box(IVec2::ZERO)))
     });
-}
 
-fn chunk_task(
-    a: i32,
-    chunks: Arc<DashMap<IVec2, Chunk>>,
-) {
-    let mut chunk = Chunk::new();
-    chunk.generate(IVec3::new(a, 0, 0));
-    let tmp_mesh = chunk.generate_mesh();
-
-    chunks.insert(IVec2::new(a, 0), chunk);
-
-    let mut mesh = Mesh::new(TriangleList);
-    mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, tmp_mesh.vertices);
-    mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, tmp_mesh.normals);
-    mesh.set_attribute("Vertex_UV", tmp_mesh.uvs);
-    mesh.set_attribute("Vertex_AO", VertexAttributeValues::from(tmp_mesh.ao));
-    mesh.set_indices(Some(Indices::U32(tmp_mesh.indices)));
+    let world = World::new(WorldPreset::Default, 1, false);
+    world.load_square(IVec2::ZERO, 1);
+    c.bench_function("Chunk Meshing", |b| {
+        b.iter(|| {
+            let chunk = world.chunks.get(&black_box(IVec2::ZERO)).unwrap();
+            chunk.generate_mesh(&world).into_mesh()
+        })
+    });
 }
 
 criterion_group!(benches, criterion_benchmark);
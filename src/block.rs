@@ -0,0 +1,213 @@
+pub const AIR: u16 = 0;
+pub const GRASS: u16 = 1;
+pub const STONE: u16 = 2;
+pub const DIRT: u16 = 3;
+pub const PLANKS: u16 = 4;
+pub const FURNACE: u16 = 5;
+pub const CRAFTING_TABLE: u16 = 6;
+pub const LOG: u16 = 7;
+pub const LEAVES: u16 = 8;
+pub const TALL_GRASS: u16 = 9;
+pub const COBBLESTONE: u16 = 10;
+pub const MOSSY_COBBLESTONE: u16 = 11;
+pub const GRAVEL: u16 = 12;
+pub const TORCH: u16 = 13;
+pub const GLOWSTONE: u16 = 14;
+pub const WATER: u16 = 15;
+
+#[derive(Clone, Copy)]
+pub enum BlockTexture {
+    Single(u16),            // all
+    Sides(u16, u16, u16),   // top, bottom, sides
+    Opisite(u16, u16, u16), // front and bottom, right and left, top and bottom
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum BlockModel {
+    None,
+    Cube,
+    Cross,
+}
+
+pub struct Block {
+    pub name: &'static str,
+    pub texture: BlockTexture,
+    pub model: BlockModel,
+    /// Hides the faces of neighbouring blocks and darkens their AO
+    pub opaque: bool,
+    /// Blocks movement
+    pub solid: bool,
+    /// Block light given off, from 0 to 15
+    pub emission: u8,
+    /// Can be swum through
+    pub fluid: bool,
+}
+
+pub const BLOCKS: [Block; 16] = [
+    Block {
+        name: "air",
+        texture: BlockTexture::Single(153),
+        model: BlockModel::None,
+        opaque: false,
+        solid: false,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "grass",
+        texture: BlockTexture::Sides(0, 2, 3),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "stone",
+        texture: BlockTexture::Single(1),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "dirt",
+        texture: BlockTexture::Single(2),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "planks",
+        texture: BlockTexture::Single(4),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "furnace",
+        texture: BlockTexture::Opisite(2 * 16 + 12, 2 * 16 + 13, 3 * 16 + 14),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "crafting_table",
+        texture: BlockTexture::Opisite(3 * 16 + 12, 3 * 16 + 11, 2 * 16 + 11),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "log",
+        texture: BlockTexture::Opisite(16 + 4, 16 + 4, 16 + 5),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "leaves",
+        texture: BlockTexture::Single(3 * 16 + 5),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "tall_grass",
+        texture: BlockTexture::Single(2 * 16 + 7),
+        model: BlockModel::Cross,
+        opaque: false,
+        solid: false,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "cobblestone",
+        texture: BlockTexture::Single(16),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "mossy_cobblestone",
+        texture: BlockTexture::Single(2 * 16 + 4),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "gravel",
+        texture: BlockTexture::Single(16 + 3),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 0,
+        fluid: false,
+    },
+    Block {
+        name: "torch",
+        texture: BlockTexture::Single(5 * 16),
+        model: BlockModel::Cross,
+        opaque: false,
+        solid: false,
+        emission: 14,
+        fluid: false,
+    },
+    Block {
+        name: "glowstone",
+        texture: BlockTexture::Single(6 * 16 + 9),
+        model: BlockModel::Cube,
+        opaque: true,
+        solid: true,
+        emission: 15,
+        fluid: false,
+    },
+    Block {
+        name: "water",
+        texture: BlockTexture::Single(12 * 16 + 13),
+        model: BlockModel::Cube,
+        opaque: false,
+        solid: false,
+        emission: 0,
+        fluid: true,
+    },
+];
+
+#[inline]
+pub fn is_opaque(block: u16) -> bool {
+    BLOCKS[block as usize].opaque
+}
+
+#[inline]
+pub fn emission(block: u16) -> u8 {
+    BLOCKS[block as usize].emission
+}
+
+#[inline]
+pub fn is_fluid(block: u16) -> bool {
+    BLOCKS[block as usize].fluid
+}
+
+pub fn from_name(name: &str) -> Option<u16> {
+    BLOCKS
+        .iter()
+        .position(|block| block.name == name)
+        .map(|id| id as u16)
+}
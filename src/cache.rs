@@ -0,0 +1,64 @@
+use bevy::prelude::{IVec2, IVec3};
+use std::collections::{BTreeMap, HashMap};
+
+use super::chunk::Chunk;
+
+/// A decorated chunk that left range, with the blocks its features placed in its neighbours
+pub struct CachedChunk {
+    pub chunk: Chunk,
+    pub spill: Vec<(IVec2, IVec3, u16)>,
+}
+
+/// Recently unloaded chunks, so coming back to an area doesn't generate and decorate it again.
+/// The least recently unloaded chunks are dropped once the cache is over its budget.
+#[derive(Default)]
+pub struct ChunkCache {
+    chunks: HashMap<IVec2, (u64, CachedChunk)>,
+    /// Chunk ids by when they were cached, oldest first
+    by_age: BTreeMap<u64, IVec2>,
+    next_age: u64,
+    /// In bytes
+    budget: usize,
+}
+
+impl ChunkCache {
+    /// Bytes taken up by the cached chunks, not counting their spill
+    pub fn memory(&self) -> usize {
+        self.chunks.len() * Chunk::MEMORY
+    }
+
+    pub fn set_budget(&mut self, budget: usize) {
+        if self.budget != budget {
+            self.budget = budget;
+            self.evict();
+        }
+    }
+
+    pub fn insert(&mut self, chunk_id: IVec2, cached: CachedChunk) {
+        let age = self.next_age;
+        self.next_age += 1;
+
+        if let Some((previous, _)) = self.chunks.insert(chunk_id, (age, cached)) {
+            self.by_age.remove(&previous);
+        }
+        self.by_age.insert(age, chunk_id);
+        self.evict();
+    }
+
+    pub fn take(&mut self, chunk_id: IVec2) -> Option<CachedChunk> {
+        let (age, cached) = self.chunks.remove(&chunk_id)?;
+        self.by_age.remove(&age);
+        Some(cached)
+    }
+
+    fn evict(&mut self) {
+        while self.memory() > self.budget {
+            let (age, chunk_id) = match self.by_age.iter().next() {
+                Some((age, chunk_id)) => (*age, *chunk_id),
+                None => break,
+            };
+            self.by_age.remove(&age);
+            self.chunks.remove(&chunk_id);
+        }
+    }
+}
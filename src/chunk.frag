@@ -4,13 +4,45 @@ layout(location = 0) out vec4 o_Target;
 layout(location = 0) in float v_ao;
 layout(location = 1) in vec3 v_normal;
 layout(location = 2) in vec2 v_uv;
+layout(location = 3) in vec2 v_light;
+layout(location = 4) in vec3 v_world_position;
+
+layout(set = 0, binding = 1) uniform CameraPosition {
+    vec4 CameraPos;
+};
 
 layout(set = 2, binding = 0) uniform texture2D ChunkMaterial_texture_atlas;
 layout(set = 2, binding = 1) uniform sampler ChunkMaterial_texture_atlas_sampler;
+layout(set = 2, binding = 2) uniform ChunkMaterial_light_direction {
+    vec3 light_direction;
+};
+layout(set = 2, binding = 3) uniform ChunkMaterial_sky_light {
+    float sky_light;
+};
+layout(set = 2, binding = 4) uniform ChunkMaterial_fog_colour {
+    vec4 fog_colour;
+};
+layout(set = 2, binding = 5) uniform ChunkMaterial_fog_start {
+    float fog_start;
+};
+layout(set = 2, binding = 6) uniform ChunkMaterial_fog_end {
+    float fog_end;
+};
 
 void main() {
     vec4 texture_colour = texture(sampler2D(ChunkMaterial_texture_atlas, ChunkMaterial_texture_atlas_sampler), v_uv);
-    float light = clamp(clamp(dot(v_normal, vec3(1.0, 0.7, 0.3)), 0.0, 1.0) + 0.3, 0.0, 1.0) * v_ao;
-    vec3 colour = texture_colour.xyz * light;
+    if (texture_colour.a < 0.5) {
+        discard;
+    }
+    // Each level of sky or block light is 80% as bright as the one above it
+    float sky = pow(0.8, (1.0 - v_light.x) * 15.0) * sky_light;
+    float block = pow(0.8, (1.0 - v_light.y) * 15.0);
+    // Block light is warm, so torchlit caves read differently from daylight
+    vec3 brightness = max(max(vec3(sky), block * vec3(1.0, 0.85, 0.6)), vec3(0.05));
+    float light = clamp(clamp(dot(v_normal, light_direction), 0.0, 1.0) + 0.3, 0.0, 1.0) * v_ao;
+    vec3 colour = texture_colour.xyz * light * brightness;
+    // Fog only counts horizontal distance so it lines up with the circle of loaded chunks
+    float horizontal_distance = length(v_world_position.xz - CameraPos.xz);
+    colour = mix(colour, fog_colour.xyz, smoothstep(fog_start, fog_end, horizontal_distance));
     o_Target = vec4(colour, 1.0);
 }
\ No newline at end of file
@@ -1,12 +1,23 @@
-use bevy::prelude::{IVec2, IVec3};
+use bevy::{
+    prelude::{IVec2, IVec3, Mesh},
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        pipeline::PrimitiveTopology::TriangleList,
+    },
+};
 use simdnoise::NoiseBuilder;
 
-use super::World;
+use super::block::*;
+use super::coords::BlockPos;
+use super::light::{LightChannel, MAX_LIGHT};
+use super::world::World;
 
 pub const CHUNK_SIZE_X: usize = 32;
 pub const CHUNK_SIZE_Y: usize = 96;
 pub const CHUNK_SIZE_Z: usize = 32;
 const AO: bool = true;
+/// Averages light over the blocks around each vertex, needs AO
+const SMOOTH_LIGHT: bool = true;
 
 #[derive(Clone, Copy)]
 enum Face {
@@ -18,12 +29,6 @@ enum Face {
     Bottom,
 }
 
-enum BlockTexture {
-    Single(u16),            // all
-    Sides(u16, u16, u16),   // top, bottom, sides
-    Opisite(u16, u16, u16), // front and bottom, right and left, top and bottom
-}
-
 const FACES: [Face; 6] = [
     Face::Front,
     Face::Back,
@@ -49,15 +54,6 @@ const CORNERS: [[[i32; 3]; 4]; 6] = [
     [[-1, 1, -1], [-1, 1, 1], [1, 1, 1], [1, 1, -1]],
     [[-1, -1, -1], [1, -1, -1], [1, -1, 1], [-1, -1, 1]],
 ];
-const BLOCKS: [BlockTexture; 7] = [
-    BlockTexture::Single(153),
-    BlockTexture::Sides(0, 2, 3),
-    BlockTexture::Single(1),
-    BlockTexture::Single(2),
-    BlockTexture::Single(4),
-    BlockTexture::Opisite(2 * 16 + 12, 2 * 16 + 13, 3 * 16 + 14),
-    BlockTexture::Opisite(3 * 16 + 12, 3 * 16 + 11, 2 * 16 + 11),
-];
 const MASK: [[[i32; 3]; 2]; 6] = [
     [[0, 1, 1], [1, 0, 1]],
     [[0, 1, 1], [1, 0, 1]],
@@ -67,16 +63,109 @@ const MASK: [[[i32; 3]; 2]; 6] = [
     [[0, 1, 1], [1, 1, 0]],
 ];
 
+#[derive(Clone, Copy)]
+pub enum Heightmap {
+    /// Highest block that hides what is behind it
+    Opaque,
+    /// Highest block that stops movement
+    MotionBlocking,
+}
+
+impl Heightmap {
+    #[inline]
+    fn counts(self, block: u16) -> bool {
+        match self {
+            Heightmap::Opaque => BLOCKS[block as usize].opaque,
+            Heightmap::MotionBlocking => BLOCKS[block as usize].solid,
+        }
+    }
+}
+
 pub struct Chunk {
     chunk_id: IVec2, // :(
     pub values: Box<[[[u16; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]>,
+    // One above the highest matching block in each column, 0 for none
+    heightmaps: [[[u8; CHUNK_SIZE_Z]; CHUNK_SIZE_X]; 2],
+    // Sky light in the high nibble, block light in the low nibble
+    light: Box<[[[u8; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]>,
 }
 
 impl Chunk {
+    /// Bytes a chunk takes up, including its boxed blocks and light
+    pub const MEMORY: usize = std::mem::size_of::<Chunk>()
+        + std::mem::size_of::<[[[u16; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]>()
+        + std::mem::size_of::<[[[u8; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]>();
+
     pub fn new(chunk_id: IVec2) -> Self {
         Chunk {
             chunk_id: chunk_id,
             values: Box::new([[[0; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]),
+            heightmaps: [[[0; CHUNK_SIZE_Z]; CHUNK_SIZE_X]; 2],
+            light: Box::new([[[0; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]),
+        }
+    }
+
+    #[inline]
+    pub fn light(&self, channel: LightChannel, x: usize, y: usize, z: usize) -> u8 {
+        match channel {
+            LightChannel::Sky => self.light[x][y][z] >> 4,
+            LightChannel::Block => self.light[x][y][z] & 0xf,
+        }
+    }
+
+    #[inline]
+    pub fn set_light(&mut self, channel: LightChannel, x: usize, y: usize, z: usize, level: u8) {
+        let packed = &mut self.light[x][y][z];
+        *packed = match channel {
+            LightChannel::Sky => (*packed & 0xf) | (level << 4),
+            LightChannel::Block => (*packed & 0xf0) | level,
+        };
+    }
+
+    /// Forgets all light, so it can be worked out again from scratch
+    pub fn clear_light(&mut self) {
+        self.light = Box::new([[[0; CHUNK_SIZE_Z]; CHUNK_SIZE_Y]; CHUNK_SIZE_X]);
+    }
+
+    /// Sets a block by local position, keeping the heightmaps current
+    pub fn set_block(&mut self, x: usize, y: usize, z: usize, block: u16) {
+        self.values[x][y][z] = block;
+
+        for heightmap in [Heightmap::Opaque, Heightmap::MotionBlocking] {
+            let height = &mut self.heightmaps[heightmap as usize][x][z];
+            if heightmap.counts(block) {
+                if y as u8 >= *height {
+                    *height = y as u8 + 1;
+                }
+            } else if y as u8 + 1 == *height {
+                *height = (0..y)
+                    .rev()
+                    .find(|&y| heightmap.counts(self.values[x][y][z]))
+                    .map_or(0, |y| y as u8 + 1);
+            }
+        }
+    }
+
+    /// Rebuilds the heightmaps from scratch, for after `values` has been written directly
+    pub fn compute_heightmaps(&mut self) {
+        for heightmap in [Heightmap::Opaque, Heightmap::MotionBlocking] {
+            for x in 0..CHUNK_SIZE_X {
+                for z in 0..CHUNK_SIZE_Z {
+                    self.heightmaps[heightmap as usize][x][z] = (0..CHUNK_SIZE_Y)
+                        .rev()
+                        .find(|&y| heightmap.counts(self.values[x][y][z]))
+                        .map_or(0, |y| y as u8 + 1);
+                }
+            }
+        }
+    }
+
+    /// Local y of the highest block in the column that counts for `heightmap`
+    #[inline]
+    pub fn height(&self, heightmap: Heightmap, x: usize, z: usize) -> Option<usize> {
+        match self.heightmaps[heightmap as usize][x][z] {
+            0 => None,
+            height => Some(height as usize - 1),
         }
     }
 
@@ -87,47 +176,86 @@ impl Chunk {
         }
 
         if pos.x < 0 || pos.x >= CHUNK_SIZE_X as i32 || pos.z < 0 || pos.z >= CHUNK_SIZE_Z as i32 {
-            let x = div_floor(pos.x, CHUNK_SIZE_X as i32);
-            let z = div_floor(pos.z, CHUNK_SIZE_Z as i32);
-            let offset_chunk_id = self.chunk_id + IVec2::new(x, z);
-            if let Some(chunk) = world.chunks.get(&offset_chunk_id) {
-                let pos = pos - IVec3::new(x * CHUNK_SIZE_X as i32, 0, z * CHUNK_SIZE_Z as i32);
-                return Some(chunk.values[pos.x as usize][pos.y as usize][pos.z as usize]);
-            } else {
-                return None;
-            }
+            let (offset, local) = BlockPos(pos).split();
+            let (x, y, z) = local.index();
+            return world
+                .chunks
+                .get(&(self.chunk_id + offset.0))
+                .map(|chunk| chunk.values[x][y][z]);
         }
 
         Some(self.values[pos.x as usize][pos.y as usize][pos.z as usize])
     }
 
-    pub fn generate(&mut self, pos: IVec3) {
-        fn evaluate(noise: &Vec<f32>, x: i32, y: i32, z: i32) -> u16 {
+    /// Sky and block light at a position relative to this chunk, from 0 to 1
+    #[inline(always)]
+    fn try_light(&self, world: &World, pos: IVec3) -> [f32; 2] {
+        if pos.y >= CHUNK_SIZE_Y as i32 {
+            return [1.0, 0.0];
+        }
+        if pos.y < 0 {
+            return [0.0, 0.0];
+        }
+
+        let packed = if pos.x < 0
+            || pos.x >= CHUNK_SIZE_X as i32
+            || pos.z < 0
+            || pos.z >= CHUNK_SIZE_Z as i32
+        {
+            let (offset, local) = BlockPos(pos).split();
+            let (x, y, z) = local.index();
+            if let Some(chunk) = world.chunks.get(&(self.chunk_id + offset.0)) {
+                chunk.light[x][y][z]
+            } else {
+                return [0.0, 0.0];
+            }
+        } else {
+            self.light[pos.x as usize][pos.y as usize][pos.z as usize]
+        };
+
+        [
+            (packed >> 4) as f32 / MAX_LIGHT as f32,
+            (packed & 0xf) as f32 / MAX_LIGHT as f32,
+        ]
+    }
+
+    /// Average light of the samples that aren't marked as hidden
+    #[inline(always)]
+    fn smooth_light(&self, world: &World, samples: [(IVec3, bool); 4]) -> [f32; 2] {
+        let mut sum = [0.0, 0.0];
+        let mut count = 0.0;
+        for (pos, hidden) in samples {
+            if !hidden {
+                let light = self.try_light(world, pos);
+                sum[0] += light[0];
+                sum[1] += light[1];
+                count += 1.0;
+            }
+        }
+        [sum[0] / count, sum[1] / count]
+    }
+
+    /// Noise terrain with its hills and valleys stretched vertically by `amplitude`, heightmaps
+    /// included
+    pub fn generate_scaled(&mut self, pos: IVec3, amplitude: f32, seed: i32) {
+        fn evaluate(noise: &Vec<f32>, amplitude: f32, x: i32, y: i32, z: i32) -> u16 {
             let p = noise
                 [x as usize + y as usize * CHUNK_SIZE_X + z as usize * CHUNK_SIZE_X * CHUNK_SIZE_Y];
-            if p + y as f32 * 0.12 - 5.0 < 0.0 {
+            if is_terrain(p, amplitude, y) {
                 1
             } else {
                 0
             }
         }
 
-        let (noise, _, _) = NoiseBuilder::gradient_3d_offset(
-            pos.x as f32,
-            CHUNK_SIZE_X,
-            0.0,
-            CHUNK_SIZE_Y,
-            pos.z as f32,
-            CHUNK_SIZE_Z,
-        )
-        .generate();
+        let noise = terrain_noise(pos, CHUNK_SIZE_X, CHUNK_SIZE_Z, seed);
 
         // values
         for x in 0..CHUNK_SIZE_X {
             for y in 0..CHUNK_SIZE_Y {
                 for z in 0..CHUNK_SIZE_Z {
                     let value = evaluate(
-                        &noise, x as i32, // + pos.x
+                        &noise, amplitude, x as i32, // + pos.x
                         y as i32, // + pos.y
                         z as i32, // + pos.z
                     );
@@ -169,6 +297,8 @@ impl Chunk {
         //         }
         //     }
         // }
+
+        self.compute_heightmaps();
     }
 
     pub fn generate_mesh(&self, world: &World) -> TmpMesh {
@@ -186,39 +316,64 @@ impl Chunk {
         for x in 0..CHUNK_SIZE_X {
             for y in 0..CHUNK_SIZE_Y {
                 for z in 0..CHUNK_SIZE_Z {
-                    if self.values[x][y][z] != 0 {
+                    let block = &BLOCKS[self.values[x][y][z] as usize];
+                    if block.model == BlockModel::Cross {
+                        let pos = IVec3::new(x as i32, y as i32, z as i32);
+                        tmp_mesh.add_cross(
+                            pos,
+                            self.try_light(world, pos),
+                            Chunk::texture(Face::Front, &block.texture),
+                        );
+                    } else if block.model == BlockModel::Cube {
                         let pos = IVec3::new(x as i32, y as i32, z as i32);
                         for face in FACES {
                             let dir = FACE_DIR[face as usize].into();
                             let dir_pos = pos + dir;
                             let dir_value = self.try_index(world, dir_pos).unwrap_or(1);
 
-                            if dir_value == 0 {
+                            // Faces between two of the same see-through block, like water,
+                            // are never seen
+                            if !is_opaque(dir_value) && dir_value != self.values[x][y][z] {
                                 let mut ao = [0, 0, 0, 0];
+                                // Faces take the light of the block they face
+                                let mut light = [self.try_light(world, dir_pos); 4];
                                 if AO {
                                     for i in 0..4 {
                                         let offset: IVec3 = CORNERS[face as usize][i].into();
-                                        let e1 = self
-                                            .try_index(
-                                                world,
-                                                offset * IVec3::from(MASK[face as usize][0]) + pos,
-                                            )
-                                            .unwrap_or(0)
-                                            != 0;
-                                        let e2 = self
-                                            .try_index(
-                                                world,
-                                                offset * IVec3::from(MASK[face as usize][1]) + pos,
-                                            )
-                                            .unwrap_or(0)
-                                            != 0;
+                                        let e1_pos =
+                                            offset * IVec3::from(MASK[face as usize][0]) + pos;
+                                        let e2_pos =
+                                            offset * IVec3::from(MASK[face as usize][1]) + pos;
+                                        let c_pos = offset + pos;
+                                        let e1 =
+                                            self.try_index(world, e1_pos).map_or(false, is_opaque);
+                                        let e2 =
+                                            self.try_index(world, e2_pos).map_or(false, is_opaque);
                                         let c =
-                                            self.try_index(world, offset + pos).unwrap_or(0) != 0;
+                                            self.try_index(world, c_pos).map_or(false, is_opaque);
                                         ao[i as usize] = get_ao(e1, e2, c);
+
+                                        if SMOOTH_LIGHT {
+                                            // The corner can't be seen past two opaque edges
+                                            light[i] = self.smooth_light(
+                                                world,
+                                                [
+                                                    (dir_pos, false),
+                                                    (e1_pos, e1),
+                                                    (e2_pos, e2),
+                                                    (c_pos, c || (e1 && e2)),
+                                                ],
+                                            );
+                                        }
                                     }
                                 }
 
-                                let flip = ao[0] + ao[2] > ao[1] + ao[3];
+                                // Split the quad along its brighter diagonal, counting both AO and
+                                // light, so dark corners don't bleed across the whole face
+                                let brightness = [0, 1, 2, 3]
+                                    .map(|i| AO_LEVELS[ao[i]] * light[i][0].max(light[i][1]));
+                                let flip =
+                                    brightness[0] + brightness[2] < brightness[1] + brightness[3];
                                 tmp_mesh.add_face(
                                     face,
                                     IVec3::new(pos.x, pos.y, pos.z),
@@ -228,8 +383,9 @@ impl Chunk {
                                         AO_LEVELS[ao[2]],
                                         AO_LEVELS[ao[3]],
                                     ],
+                                    light,
                                     flip,
-                                    Chunk::texture(face, &BLOCKS[self.values[x][y][z] as usize]),
+                                    Chunk::texture(face, &block.texture),
                                 );
                             }
                         }
@@ -270,27 +426,115 @@ pub struct TmpMesh {
     pub normals: Vec<[f32; 3]>,
     pub uvs: Vec<[f32; 2]>,
     pub ao: Vec<f32>,
+    pub light: Vec<[f32; 2]>,
     pub indices: Vec<u32>,
 }
 
 impl TmpMesh {
+    /// The mesh with the attributes `chunk.vert` expects, or None if there's nothing to draw
+    pub fn into_mesh(self) -> Option<Mesh> {
+        if self.indices.is_empty() {
+            return None;
+        }
+
+        let mut mesh = Mesh::new(TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, self.vertices);
+        mesh.set_attribute(Mesh::ATTRIBUTE_NORMAL, self.normals);
+        mesh.set_attribute("Vertex_UV", self.uvs);
+        mesh.set_attribute("Vertex_AO", VertexAttributeValues::from(self.ao));
+        mesh.set_attribute("Vertex_Light", self.light);
+        mesh.set_indices(Some(Indices::U32(self.indices)));
+        Some(mesh)
+    }
+
     fn new(capacity: usize) -> Self {
         TmpMesh {
             vertices: Vec::with_capacity(capacity),
             normals: Vec::with_capacity(capacity),
             uvs: Vec::with_capacity(capacity),
             ao: Vec::with_capacity(capacity),
+            light: Vec::with_capacity(capacity),
             indices: Vec::with_capacity(capacity * 2),
         }
     }
 
     #[inline]
-    fn add_face(&mut self, face: Face, o: IVec3, ao: [f32; 4], flip: bool, texture_id: u16) {
+    fn uvs(texture_id: u16) -> [[f32; 2]; 4] {
+        let tex_y = (texture_id / 16) as f32;
+        let tex_x = texture_id as f32 - tex_y * 16.0;
+
+        let tl = [(0.0 + tex_x) / 16.0, (0.0 + tex_y) / 16.0];
+        let tr = [(1.0 + tex_x) / 16.0, (0.0 + tex_y) / 16.0];
+        let bl = [(0.0 + tex_x) / 16.0, (1.0 + tex_y) / 16.0];
+        let br = [(1.0 + tex_x) / 16.0, (1.0 + tex_y) / 16.0];
+
+        [tl, tr, bl, br]
+    }
+
+    /// Two diagonal quads, each emitted with both windings so they show from either side
+    #[inline]
+    fn add_cross(&mut self, o: IVec3, light: [f32; 2], texture_id: u16) {
+        let x = o.x as f32;
+        let y = o.y as f32;
+        let z = o.z as f32;
+
+        let [tl, tr, bl, br] = TmpMesh::uvs(texture_id);
+
+        let quads = [
+            [
+                [0.0 + x, 0.0 + y, 0.0 + z],
+                [1.0 + x, 0.0 + y, 1.0 + z],
+                [1.0 + x, 1.0 + y, 1.0 + z],
+                [0.0 + x, 1.0 + y, 0.0 + z],
+            ],
+            [
+                [0.0 + x, 0.0 + y, 1.0 + z],
+                [1.0 + x, 0.0 + y, 0.0 + z],
+                [1.0 + x, 1.0 + y, 0.0 + z],
+                [0.0 + x, 1.0 + y, 1.0 + z],
+            ],
+        ];
+
+        for quad in quads {
+            let a = self.vertices.len() as u32;
+            self.indices.extend([
+                a,
+                a + 1,
+                a + 2,
+                a,
+                a + 2,
+                a + 3,
+                a,
+                a + 2,
+                a + 1,
+                a,
+                a + 3,
+                a + 2,
+            ]);
+            self.vertices.extend(quad);
+            self.normals.extend([[0.0, 1.0, 0.0]; 4]);
+            self.uvs.extend([bl, br, tr, tl]);
+            self.ao.extend([1.0; 4]);
+            self.light.extend([light; 4]);
+        }
+    }
+
+    #[inline]
+    fn add_face(
+        &mut self,
+        face: Face,
+        o: IVec3,
+        ao: [f32; 4],
+        light: [[f32; 2]; 4],
+        flip: bool,
+        texture_id: u16,
+    ) {
         let x = o.x as f32;
         let y = o.y as f32;
         let z = o.z as f32;
 
         self.ao.extend(ao);
+        self.light.extend(light);
 
         let a = self.vertices.len() as u32;
         self.indices.extend(match flip {
@@ -298,13 +542,7 @@ impl TmpMesh {
             true => [a + 1, a + 3, a, a + 1, a + 2, a + 3],
         });
 
-        let tex_y = (texture_id / 16) as f32;
-        let tex_x = texture_id as f32 - tex_y * 16.0;
-
-        let tl = [(0.0 + tex_x) / 16.0, (0.0 + tex_y) / 16.0];
-        let tr = [(1.0 + tex_x) / 16.0, (0.0 + tex_y) / 16.0];
-        let bl = [(0.0 + tex_x) / 16.0, (1.0 + tex_y) / 16.0];
-        let br = [(1.0 + tex_x) / 16.0, (1.0 + tex_y) / 16.0];
+        let [tl, tr, bl, br] = TmpMesh::uvs(texture_id);
 
         match face {
             Face::Front => {
@@ -401,12 +639,24 @@ impl TmpMesh {
     }
 }
 
-pub const fn div_floor(lhs: i32, rhs: i32) -> i32 {
-    let d = lhs / rhs;
-    let r = lhs % rhs;
-    if (r > 0 && rhs < 0) || (r < 0 && rhs > 0) {
-        d - 1
-    } else {
-        d
-    }
+/// 3D noise for a `size_x` by `CHUNK_SIZE_Y` by `size_z` box starting at `pos`, indexed by
+/// `x + y * size_x + z * size_x * CHUNK_SIZE_Y`
+pub fn terrain_noise(pos: IVec3, size_x: usize, size_z: usize, seed: i32) -> Vec<f32> {
+    let (noise, _, _) = NoiseBuilder::gradient_3d_offset(
+        pos.x as f32,
+        size_x,
+        0.0,
+        CHUNK_SIZE_Y,
+        pos.z as f32,
+        size_z,
+    )
+    .with_seed(seed)
+    .generate();
+
+    noise
+}
+
+#[inline]
+pub fn is_terrain(noise: f32, amplitude: f32, y: i32) -> bool {
+    noise * amplitude + y as f32 * 0.12 - 5.0 < 0.0
 }
@@ -4,9 +4,12 @@ layout(location = 0) in vec3 Vertex_Position;
 layout(location = 1) in vec3 Vertex_Normal;
 layout(location = 2) in vec2 Vertex_UV;
 layout(location = 3) in float Vertex_AO;
+layout(location = 4) in vec2 Vertex_Light;
 layout(location = 0) out float v_ao;
 layout(location = 1) out vec3 v_normal;
 layout(location = 2) out vec2 v_uv;
+layout(location = 3) out vec2 v_light;
+layout(location = 4) out vec3 v_world_position;
 
 
 layout(set = 0, binding = 0) uniform CameraViewProj {
@@ -18,8 +21,11 @@ layout(set = 1, binding = 0) uniform Transform {
 };
 
 void main() {
-    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+    vec4 world_position = Model * vec4(Vertex_Position, 1.0);
+    gl_Position = ViewProj * world_position;
+    v_world_position = world_position.xyz;
     v_ao = Vertex_AO;
     v_normal = Vertex_Normal;
     v_uv = Vertex_UV;
+    v_light = Vertex_Light;
 }
\ No newline at end of file
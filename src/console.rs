@@ -0,0 +1,57 @@
+use bevy::prelude::*;
+use std::{
+    io::BufRead,
+    sync::{
+        mpsc::{channel, Receiver},
+        Mutex,
+    },
+};
+
+use super::daytime::WorldTime;
+use super::settings::Settings;
+
+/// Commands typed into the terminal the game was started from
+pub struct Console {
+    lines: Mutex<Receiver<String>>,
+}
+
+impl Console {
+    /// Reads stdin on its own thread, as reading it blocks
+    pub fn spawn() -> Self {
+        let (sender, lines) = channel();
+        std::thread::spawn(move || {
+            for line in std::io::stdin().lock().lines() {
+                match line {
+                    Ok(line) if sender.send(line).is_ok() => {}
+                    _ => break,
+                }
+            }
+        });
+
+        Console {
+            lines: Mutex::new(lines),
+        }
+    }
+}
+
+pub fn console_system(
+    console: Res<Console>,
+    mut world_time: ResMut<WorldTime>,
+    mut settings: ResMut<Settings>,
+) {
+    let lines: Vec<String> = console.lines.lock().unwrap().try_iter().collect();
+    for line in lines {
+        let args: Vec<&str> = line.split_whitespace().collect();
+        let result = match args.split_first() {
+            Some((&"time", args)) => world_time.command(args),
+            Some((&command @ ("set" | "bind"), args)) => settings.command(command, args),
+            Some((command, _)) => Err(format!("Unknown command {:?}", command)),
+            None => continue,
+        };
+
+        match result {
+            Ok(message) => println!("{}", message),
+            Err(err) => eprintln!("{}", err),
+        }
+    }
+}
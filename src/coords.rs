@@ -0,0 +1,178 @@
+use bevy::prelude::{IVec2, IVec3, Vec3};
+
+use super::chunk::{CHUNK_SIZE_X, CHUNK_SIZE_Z};
+
+/// A block, either in world space or relative to the corner of some chunk
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct BlockPos(pub IVec3);
+
+/// A chunk, counted in chunks along x and z. Chunks span the whole height of the world.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct ChunkPos(pub IVec2);
+
+/// A block relative to the corner of the chunk holding it, with x and z inside the chunk.
+/// y is left as it is. Chunks are stored as whole columns rather than stacked sections, so this
+/// stands in for a section position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct LocalPos(pub IVec3);
+
+impl BlockPos {
+    /// The block a point is in, rounding down so -0.5 is in block -1 rather than block 0
+    #[inline]
+    pub fn from_world(pos: Vec3) -> Self {
+        BlockPos(IVec3::new(
+            pos.x.floor() as i32,
+            pos.y.floor() as i32,
+            pos.z.floor() as i32,
+        ))
+    }
+
+    #[inline]
+    pub fn chunk(self) -> ChunkPos {
+        ChunkPos(IVec2::new(
+            div_floor(self.0.x, CHUNK_SIZE_X as i32),
+            div_floor(self.0.z, CHUNK_SIZE_Z as i32),
+        ))
+    }
+
+    /// The chunk holding the block and where the block is inside it. Relative to a chunk, that's
+    /// the neighbour it's in as an offset.
+    #[inline]
+    pub fn split(self) -> (ChunkPos, LocalPos) {
+        let chunk = self.chunk();
+        (chunk, LocalPos(self.0 - chunk.origin().0))
+    }
+}
+
+impl ChunkPos {
+    /// The chunk holding a point, which is never the one towards zero for negative coordinates
+    #[inline]
+    pub fn from_world(pos: Vec3) -> Self {
+        BlockPos::from_world(pos).chunk()
+    }
+
+    /// The chunk's lowest corner block
+    #[inline]
+    pub fn origin(self) -> BlockPos {
+        BlockPos(IVec3::new(
+            self.0.x * CHUNK_SIZE_X as i32,
+            0,
+            self.0.y * CHUNK_SIZE_Z as i32,
+        ))
+    }
+
+    /// Where the chunk's mesh goes
+    #[inline]
+    pub fn translation(self) -> Vec3 {
+        let origin = self.origin().0;
+        Vec3::new(origin.x as f32, origin.y as f32, origin.z as f32)
+    }
+}
+
+impl LocalPos {
+    #[inline]
+    pub fn index(self) -> (usize, usize, usize) {
+        (self.0.x as usize, self.0.y as usize, self.0.z as usize)
+    }
+}
+
+/// Division rounding towards negative infinity rather than zero
+pub const fn div_floor(lhs: i32, rhs: i32) -> i32 {
+    let d = lhs / rhs;
+    let r = lhs % rhs;
+    if (r > 0 && rhs < 0) || (r < 0 && rhs > 0) {
+        d - 1
+    } else {
+        d
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::chunk::CHUNK_SIZE_Y;
+
+    const SIZE_X: i32 = CHUNK_SIZE_X as i32;
+    const SIZE_Z: i32 = CHUNK_SIZE_Z as i32;
+
+    #[test]
+    fn div_floor_rounds_down() {
+        for rhs in [1, 2, 3, 7, SIZE_X, -1, -3, -SIZE_X] {
+            for lhs in -200..=200 {
+                let expected = (lhs as f64 / rhs as f64).floor() as i32;
+                assert_eq!(div_floor(lhs, rhs), expected, "{} / {}", lhs, rhs);
+            }
+        }
+    }
+
+    #[test]
+    fn block_from_world_contains_point() {
+        for i in -400..=400 {
+            let v = i as f32 * 0.25;
+            let block = BlockPos::from_world(Vec3::new(v, v.abs(), -v)).0;
+            assert!(block.x as f32 <= v && v < block.x as f32 + 1.0, "{}", v);
+            assert!(block.z as f32 <= -v && -v < block.z as f32 + 1.0, "{}", v);
+        }
+        assert_eq!(
+            BlockPos::from_world(Vec3::new(-0.5, 0.5, -0.5)),
+            BlockPos(IVec3::new(-1, 0, -1))
+        );
+    }
+
+    #[test]
+    fn split_round_trips() {
+        for x in -3 * SIZE_X..3 * SIZE_X {
+            for z in [-SIZE_Z - 1, -SIZE_Z, -1, 0, 1, SIZE_Z - 1, SIZE_Z, x] {
+                for y in [0, CHUNK_SIZE_Y as i32 - 1] {
+                    let pos = IVec3::new(x, y, z);
+                    let (chunk, local) = BlockPos(pos).split();
+                    assert_eq!(chunk.origin().0 + local.0, pos);
+                    assert_eq!(chunk, BlockPos(pos).chunk());
+                    assert!(local.0.x >= 0 && local.0.x < SIZE_X, "{:?}", pos);
+                    assert!(local.0.z >= 0 && local.0.z < SIZE_Z, "{:?}", pos);
+                    assert_eq!(local.0.y, y);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_edges() {
+        for x in -5..5 {
+            for z in -5..5 {
+                let chunk = ChunkPos(IVec2::new(x, z));
+                let origin = chunk.origin();
+                assert_eq!(origin.chunk(), chunk);
+                assert_eq!(
+                    BlockPos(origin.0 + IVec3::new(SIZE_X - 1, 0, SIZE_Z - 1)).chunk(),
+                    chunk
+                );
+                assert_eq!(
+                    BlockPos(origin.0 - IVec3::new(1, 0, 1)).chunk(),
+                    ChunkPos(IVec2::new(x - 1, z - 1))
+                );
+                assert_eq!(ChunkPos::from_world(chunk.translation()), chunk);
+            }
+        }
+    }
+
+    #[test]
+    fn chunk_from_world_rounds_down() {
+        assert_eq!(
+            ChunkPos::from_world(Vec3::new(-0.5, 0.0, -0.5)),
+            ChunkPos(IVec2::new(-1, -1))
+        );
+        assert_eq!(
+            ChunkPos::from_world(Vec3::new(0.5, 0.0, 0.5)),
+            ChunkPos(IVec2::ZERO)
+        );
+        assert_eq!(
+            ChunkPos::from_world(Vec3::new(SIZE_X as f32 - 0.5, 0.0, -SIZE_Z as f32)),
+            ChunkPos(IVec2::new(0, -1))
+        );
+        assert_eq!(
+            ChunkPos::from_world(Vec3::new(SIZE_X as f32, 0.0, -SIZE_Z as f32 - 0.5)),
+            ChunkPos(IVec2::new(1, -2))
+        );
+    }
+}
@@ -0,0 +1,188 @@
+use bevy::prelude::*;
+use std::f32::consts::TAU;
+
+use super::settings::Settings;
+use super::sky::{self, SkyMaterial, SkyMaterialHandle, Underwater};
+use super::{ChunkMaterial, ChunkMaterialHandle};
+
+pub const DAY_LENGTH: u64 = 24000;
+
+/// Ticks into the day of each named time, the day starts at sunrise
+const NAMED_TIMES: [(&str, u64); 4] = [
+    ("day", 1000),
+    ("noon", 6000),
+    ("night", 13000),
+    ("midnight", 18000),
+];
+
+/// Sky light at midnight, as a fraction of full daylight
+const NIGHT_SKY_LIGHT: f32 = 0.15;
+
+const DAY_HORIZON: [f32; 3] = [0.7, 0.85, 1.0];
+const DAY_ZENITH: [f32; 3] = [0.3, 0.55, 0.95];
+const SUNSET_HORIZON: [f32; 3] = [0.95, 0.55, 0.3];
+const NIGHT_HORIZON: [f32; 3] = [0.04, 0.05, 0.1];
+const NIGHT_ZENITH: [f32; 3] = [0.0, 0.0, 0.03];
+
+/// Time of day in simulation ticks, advanced every tick unless frozen
+pub struct WorldTime {
+    pub ticks: u64,
+    pub frozen: bool,
+}
+
+impl WorldTime {
+    /// Starts at `time`, a number of ticks or a name like `noon`, or in the morning if there's none
+    pub fn parse(time: Option<&str>) -> Result<Self, String> {
+        let ticks = match time {
+            Some(time) => parse_time(time)?,
+            None => NAMED_TIMES[0].1,
+        };
+        Ok(WorldTime {
+            ticks,
+            frozen: false,
+        })
+    }
+
+    /// 0 at sunrise, 0.25 at noon, 0.5 at sunset and 0.75 at midnight
+    pub fn time_of_day(&self) -> f32 {
+        (self.ticks % DAY_LENGTH) as f32 / DAY_LENGTH as f32
+    }
+
+    /// Direction towards the sun, rising in +x and setting in -x
+    pub fn sun_direction(&self) -> Vec3 {
+        let angle = self.time_of_day() * TAU;
+        Vec3::new(angle.cos(), angle.sin(), 0.3).normalize()
+    }
+
+    /// Direction blocks are lit from, the sun by day and the moon by night
+    pub fn light_direction(&self) -> Vec3 {
+        let sun = self.sun_direction();
+        if sun.y >= 0.0 {
+            sun
+        } else {
+            Vec3::new(-sun.x, -sun.y, sun.z)
+        }
+    }
+
+    /// 0 at night to 1 during the day, easing through dawn and dusk
+    pub fn daylight(&self) -> f32 {
+        (self.sun_direction().y * 3.0 + 0.5).clamp(0.0, 1.0)
+    }
+
+    /// What full sky light is multiplied by
+    pub fn sky_light(&self) -> f32 {
+        NIGHT_SKY_LIGHT + (1.0 - NIGHT_SKY_LIGHT) * self.daylight()
+    }
+
+    /// Colour of the sky at the horizon, which fog fades into
+    pub fn horizon_colour(&self) -> Color {
+        let daylight = self.daylight();
+        // Reddest when the sun is on the horizon
+        let sunset = 1.0 - (self.sun_direction().y * 4.0).abs().min(1.0);
+
+        let base = Vec3::from(NIGHT_HORIZON).lerp(Vec3::from(DAY_HORIZON), daylight);
+        let colour = base.lerp(Vec3::from(SUNSET_HORIZON), sunset * 0.6);
+        Color::rgb(colour.x, colour.y, colour.z)
+    }
+
+    /// Colour of the sky straight up
+    pub fn zenith_colour(&self) -> Color {
+        let colour = Vec3::from(NIGHT_ZENITH).lerp(Vec3::from(DAY_ZENITH), self.daylight());
+        Color::rgb(colour.x, colour.y, colour.z)
+    }
+
+    /// Handles `time set <ticks|day|noon|night|midnight>`, `time add <ticks>`, `time freeze` and
+    /// `time unfreeze`. Returns what happened.
+    pub fn command(&mut self, args: &[&str]) -> Result<String, String> {
+        match args {
+            ["set", time] => {
+                self.ticks = parse_time(time)?;
+                Ok(format!("Set the time to {}", self.ticks))
+            }
+            ["add", ticks] => {
+                let ticks: u64 = ticks
+                    .parse()
+                    .map_err(|_| format!("Invalid number of ticks {:?}", ticks))?;
+                self.ticks = (self.ticks + ticks % DAY_LENGTH) % DAY_LENGTH;
+                Ok(format!("Set the time to {}", self.ticks))
+            }
+            ["freeze"] => {
+                self.frozen = true;
+                Ok(format!("Froze the time at {}", self.ticks))
+            }
+            ["unfreeze"] => {
+                self.frozen = false;
+                Ok("Unfroze the time".to_string())
+            }
+            ["query"] | [] => Ok(format!(
+                "The time is {}{}",
+                self.ticks,
+                if self.frozen { " (frozen)" } else { "" }
+            )),
+            _ => Err(
+                "Usage: time set <ticks|day|noon|night|midnight> | add <ticks> | freeze | unfreeze"
+                    .to_string(),
+            ),
+        }
+    }
+}
+
+fn parse_time(time: &str) -> Result<u64, String> {
+    if let Some((_, ticks)) = NAMED_TIMES.iter().find(|(name, _)| *name == time) {
+        return Ok(*ticks);
+    }
+
+    time.parse::<u64>()
+        .map(|ticks| ticks % DAY_LENGTH)
+        .map_err(|_| format!("Invalid time {:?}", time))
+}
+
+/// Runs once per simulation tick
+pub fn advance_time(mut world_time: ResMut<WorldTime>) {
+    if !world_time.frozen {
+        world_time.ticks = (world_time.ticks + 1) % DAY_LENGTH;
+    }
+}
+
+/// Passes the sun, sky light and fog to the chunk shader and matches the sky to them. Under water
+/// the fog closes in and takes the colour of the water instead.
+pub fn apply_time(
+    world_time: Res<WorldTime>,
+    underwater: Res<Underwater>,
+    settings: Res<Settings>,
+    chunk_material_handle: Res<ChunkMaterialHandle>,
+    sky_material_handle: Res<SkyMaterialHandle>,
+    mut chunk_materials: ResMut<Assets<ChunkMaterial>>,
+    mut sky_materials: ResMut<Assets<SkyMaterial>>,
+    mut clear_colour: ResMut<ClearColor>,
+) {
+    if !world_time.is_changed() && !underwater.is_changed() && !settings.is_changed() {
+        return;
+    }
+
+    let (horizon, zenith, (fog_start, fog_end)) = if underwater.0 {
+        // Dimmed along with the sky light, so night time water isn't glowing
+        let water = Vec3::from(sky::UNDERWATER_FOG) * world_time.sky_light();
+        let water = Color::rgb(water.x, water.y, water.z);
+        (water, water, sky::UNDERWATER_FOG_RANGE)
+    } else {
+        (
+            world_time.horizon_colour(),
+            world_time.zenith_colour(),
+            sky::fog_range(settings.view_distance),
+        )
+    };
+
+    if let Some(material) = chunk_materials.get_mut(&chunk_material_handle.0) {
+        material.light_direction = world_time.light_direction();
+        material.sky_light = world_time.sky_light();
+        material.fog_colour = horizon;
+        material.fog_start = fog_start;
+        material.fog_end = fog_end;
+    }
+    if let Some(material) = sky_materials.get_mut(&sky_material_handle.0) {
+        material.horizon = horizon;
+        material.zenith = zenith;
+    }
+    clear_colour.0 = horizon;
+}
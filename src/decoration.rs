@@ -0,0 +1,195 @@
+use bevy::prelude::{IVec2, IVec3};
+
+use super::block::*;
+use super::chunk::*;
+use super::coords::{BlockPos, ChunkPos, LocalPos};
+use super::neighbors;
+use super::world::World;
+
+// One in n grass columns gets the feature
+const TREE_CHANCE: u32 = 120;
+const BOULDER_CHANCE: u32 = 700;
+const TALL_GRASS_CHANCE: u32 = 9;
+
+/// Places trees, boulders and tall grass rooted in `chunk_id`. Only run once all eight neighbours
+/// have terrain, as features reach up to two blocks into them.
+pub fn decorate(world: &World, chunk_id: IVec2) {
+    let origin = ChunkPos(chunk_id).origin().0;
+
+    let mut placements = Vec::new();
+    if let Some(chunk) = world.chunks.get(&chunk_id) {
+        for x in 0..CHUNK_SIZE_X {
+            for z in 0..CHUNK_SIZE_Z {
+                let surface = match chunk.height(Heightmap::Opaque, x, z) {
+                    Some(y) => y,
+                    None => continue,
+                };
+                if chunk.values[x][surface][z] != GRASS {
+                    continue;
+                }
+
+                let pos = origin + IVec3::new(x as i32, surface as i32 + 1, z as i32);
+                let roll = hash(pos.x, pos.z, 0);
+                if roll % TREE_CHANCE == 0 {
+                    tree(&mut placements, pos, hash(pos.x, pos.z, 1));
+                } else if roll % BOULDER_CHANCE == 1 {
+                    boulder(&mut placements, pos, hash(pos.x, pos.z, 2));
+                } else if roll % TALL_GRASS_CHANCE == 2 {
+                    placements.push((pos, TALL_GRASS));
+                }
+            }
+        }
+    }
+
+    // The read lock on this chunk has to be released before writing, as features can land in it
+    for (pos, block) in placements {
+        place(world, chunk_id, pos, block);
+    }
+}
+
+/// Restores blocks that loaded neighbours placed into this chunk before it was last unloaded.
+pub fn apply_spill(world: &World, chunk_id: IVec2) {
+    if let Some(spill) = world.spill.get(&chunk_id) {
+        if let Some(mut chunk) = world.chunks.get_mut(&chunk_id) {
+            for (_, local, block) in spill.iter() {
+                set_if_air(&mut chunk, *local, *block);
+            }
+        }
+    }
+}
+
+/// Drops the blocks `source` placed into its neighbours, called when `source` is unloaded.
+/// Returns them tagged with the chunk they landed in, so they can be put back with
+/// `restore_spill` if `source` comes back without being decorated again.
+pub fn take_spill(world: &World, source: IVec2) -> Vec<(IVec2, IVec3, u16)> {
+    let mut taken = Vec::new();
+    for dir in neighbors {
+        let target = source + dir.into();
+        if let Some(mut spill) = world.spill.get_mut(&target) {
+            spill.retain(|(s, local, block)| {
+                if *s == source {
+                    taken.push((target, *local, *block));
+                }
+                *s != source
+            });
+        }
+        world.spill.remove_if(&target, |_, spill| spill.is_empty());
+    }
+    taken
+}
+
+/// Records and places the blocks `source` spilled into its neighbours again. Neighbours that have
+/// been lit since, according to `lit`, are changed with `World::set_block` so their light and
+/// meshes catch up.
+pub fn restore_spill(
+    world: &World,
+    source: IVec2,
+    spill: Vec<(IVec2, IVec3, u16)>,
+    lit: impl Fn(IVec2) -> bool,
+) {
+    for (target, local, block) in spill {
+        world
+            .spill
+            .entry(target)
+            .or_insert_with(Vec::new)
+            .push((source, local, block));
+        if lit(target) {
+            let pos = ChunkPos(target).origin().0 + local;
+            if world.get_block(pos) == Some(AIR) {
+                world.set_block(pos, block);
+            }
+        } else if let Some(mut chunk) = world.chunks.get_mut(&target) {
+            set_if_air(&mut chunk, local, block);
+        }
+    }
+}
+
+fn place(world: &World, source: IVec2, pos: IVec3, block: u16) {
+    if pos.y < 0 || pos.y >= CHUNK_SIZE_Y as i32 {
+        return;
+    }
+
+    let (ChunkPos(target), LocalPos(local)) = BlockPos(pos).split();
+
+    // Recorded before writing so a neighbour that is generating right now either sees the spill
+    // when it is inserted or is already in the map for the write below
+    if target != source {
+        world
+            .spill
+            .entry(target)
+            .or_insert_with(Vec::new)
+            .push((source, local, block));
+    }
+
+    if let Some(mut chunk) = world.chunks.get_mut(&target) {
+        set_if_air(&mut chunk, local, block);
+    }
+}
+
+#[inline]
+fn set_if_air(chunk: &mut Chunk, local: IVec3, block: u16) {
+    let (x, y, z) = (local.x as usize, local.y as usize, local.z as usize);
+    if chunk.values[x][y][z] == AIR {
+        chunk.set_block(x, y, z, block);
+    }
+}
+
+fn tree(placements: &mut Vec<(IVec3, u16)>, base: IVec3, seed: u32) {
+    let height = 4 + (seed % 3) as i32;
+    let top = base.y + height;
+    if top + 2 >= CHUNK_SIZE_Y as i32 {
+        return;
+    }
+
+    // Trunk goes first so the leaves don't claim its top blocks
+    for y in base.y..top {
+        placements.push((IVec3::new(base.x, y, base.z), LOG));
+    }
+
+    for dy in -2..=1 {
+        let radius = if dy < 0 { 2 } else { 1 };
+        for dx in -radius..=radius {
+            for dz in -radius..=radius {
+                let corner = dx.abs() == radius && dz.abs() == radius;
+                if corner && (dy == 1 || hash(base.x + dx, base.z + dz, seed ^ dy as u32) % 2 == 0)
+                {
+                    continue;
+                }
+                placements.push((IVec3::new(base.x + dx, top + dy, base.z + dz), LEAVES));
+            }
+        }
+    }
+}
+
+fn boulder(placements: &mut Vec<(IVec3, u16)>, base: IVec3, seed: u32) {
+    let radius = 1 + (seed % 2) as i32;
+    for dx in -radius..=radius {
+        for dy in -radius..=radius {
+            for dz in -radius..=radius {
+                let pos = base + IVec3::new(dx, dy, dz);
+                let noise = hash(pos.x, pos.z, seed ^ pos.y as u32);
+                if dx * dx + dy * dy + dz * dz > radius * radius + (noise % 2) as i32 {
+                    continue;
+                }
+                let block = if noise % 3 == 0 {
+                    MOSSY_COBBLESTONE
+                } else {
+                    COBBLESTONE
+                };
+                placements.push((pos, block));
+            }
+        }
+    }
+}
+
+/// Cheap integer hash so features are the same every time a column is generated
+#[inline]
+pub fn hash(x: i32, z: i32, salt: u32) -> u32 {
+    let mut h = (x as u32).wrapping_mul(0x8da6_b343)
+        ^ (z as u32).wrapping_mul(0xd816_3841)
+        ^ salt.wrapping_mul(0xcb1a_b31f);
+    h ^= h >> 13;
+    h = h.wrapping_mul(0x5bd1_e995);
+    h ^= h >> 15;
+    h
+}
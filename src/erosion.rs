@@ -0,0 +1,261 @@
+use bevy::prelude::{IVec2, IVec3, Vec2};
+use dashmap::DashMap;
+use std::sync::{
+    atomic::{AtomicU64, Ordering},
+    Arc,
+};
+
+use super::block::{AIR, DIRT, GRASS, GRAVEL};
+use super::chunk::*;
+use super::coords::div_floor;
+use super::decoration::hash;
+
+/// Side length in blocks of the area each simulation covers
+const REGION_SIZE: i32 = 128;
+/// Regions overlap by half, so every column is blended from the four regions covering it and the
+/// result is continuous across region edges
+const REGION_STRIDE: i32 = REGION_SIZE / 2;
+const MAX_CACHED_REGIONS: usize = 256;
+
+const DROPLETS: usize = 12000;
+const LIFETIME: usize = 30;
+const INERTIA: f32 = 0.05;
+const CAPACITY: f32 = 4.0;
+const MIN_CAPACITY: f32 = 0.01;
+const ERODE_SPEED: f32 = 0.3;
+const DEPOSIT_SPEED: f32 = 0.3;
+const EVAPORATE_SPEED: f32 = 0.01;
+const GRAVITY: f32 = 4.0;
+
+/// Water that has to pass through a column before it becomes part of a river
+const RIVER_FLOW: f32 = 12.0;
+const RIVER_DEPTH: f32 = 3.0;
+
+struct Region {
+    /// Change in surface height per column, indexed by `x + z * REGION_SIZE`
+    delta: Vec<f32>,
+    /// 0 to 1, how much of a river runs through each column
+    river: Vec<f32>,
+    last_used: AtomicU64,
+}
+
+/// Eroded regions, kept so neighbouring chunks blend from the same simulation
+#[derive(Default)]
+pub struct Erosion {
+    regions: DashMap<IVec2, Arc<Region>>,
+    clock: AtomicU64,
+}
+
+impl Erosion {
+    /// Lowers and raises the surface of freshly generated noise terrain to match the eroded
+    /// heightmap. Heightmaps have to be current.
+    pub fn apply(&self, chunk: &mut Chunk, pos: IVec3, amplitude: f32, seed: i32) {
+        // Chunks never straddle a stride boundary, so the same four regions cover all of it
+        let first = IVec2::new(
+            div_floor(pos.x, REGION_STRIDE) - 1,
+            div_floor(pos.z, REGION_STRIDE) - 1,
+        );
+        let mut regions = Vec::with_capacity(4);
+        for dx in 0..2 {
+            for dz in 0..2 {
+                let region_id = first + IVec2::new(dx, dz);
+                regions.push((region_id, self.region(region_id, amplitude, seed)));
+            }
+        }
+
+        for x in 0..CHUNK_SIZE_X {
+            for z in 0..CHUNK_SIZE_Z {
+                let top = match chunk.height(Heightmap::Opaque, x, z) {
+                    Some(top) => top as i32,
+                    None => continue,
+                };
+
+                let column = IVec2::new(pos.x + x as i32, pos.z + z as i32);
+                let mut delta: f32 = 0.0;
+                let mut river: f32 = 0.0;
+                for (region_id, region) in regions.iter() {
+                    let local = column - *region_id * REGION_STRIDE;
+                    let weight = tent(local.x) * tent(local.y);
+                    let i = (local.x + local.y * REGION_SIZE) as usize;
+                    delta += region.delta[i] * weight;
+                    river += region.river[i] * weight;
+                }
+
+                let new_top = (top + delta.round() as i32).clamp(0, CHUNK_SIZE_Y as i32 - 1);
+                for y in (new_top + 1)..=top {
+                    chunk.set_block(x, y as usize, z, AIR);
+                }
+                for y in top..new_top {
+                    chunk.set_block(x, y as usize, z, DIRT);
+                }
+                let surface = if river > 0.5 { GRAVEL } else { GRASS };
+                chunk.set_block(x, new_top as usize, z, surface);
+            }
+        }
+    }
+
+    fn region(&self, region_id: IVec2, amplitude: f32, seed: i32) -> Arc<Region> {
+        let now = self.clock.fetch_add(1, Ordering::Relaxed);
+        if let Some(region) = self.regions.get(&region_id) {
+            region.last_used.store(now, Ordering::Relaxed);
+            return region.clone();
+        }
+
+        // Two tasks may simulate the same region at once, which is wasted but harmless as the
+        // result only depends on the region and the seed
+        let region = Arc::new(simulate(region_id, amplitude, seed));
+        region.last_used.store(now, Ordering::Relaxed);
+        self.regions.insert(region_id, region.clone());
+
+        if self.regions.len() > MAX_CACHED_REGIONS {
+            let oldest = self
+                .regions
+                .iter()
+                .min_by_key(|region| region.last_used.load(Ordering::Relaxed))
+                .map(|region| *region.key());
+            if let Some(oldest) = oldest {
+                self.regions.remove(&oldest);
+            }
+        }
+
+        region
+    }
+}
+
+/// Weight of a column `local` blocks into a region, rising from 0 at the edges to 1 in the middle
+#[inline]
+fn tent(local: i32) -> f32 {
+    let u = (local as f32 + 0.5) / REGION_SIZE as f32;
+    1.0 - (2.0 * u - 1.0).abs()
+}
+
+fn simulate(region_id: IVec2, amplitude: f32, seed: i32) -> Region {
+    let size = REGION_SIZE as usize;
+    let origin = region_id * REGION_STRIDE;
+    let noise = terrain_noise(IVec3::new(origin.x, 0, origin.y), size, size, seed);
+
+    let mut heights = vec![0.0; size * size];
+    for x in 0..size {
+        for z in 0..size {
+            heights[x + z * size] = (0..CHUNK_SIZE_Y)
+                .rev()
+                .find(|&y| {
+                    is_terrain(
+                        noise[x + y * size + z * size * CHUNK_SIZE_Y],
+                        amplitude,
+                        y as i32,
+                    )
+                })
+                .map_or(0.0, |y| y as f32);
+        }
+    }
+    let original = heights.clone();
+
+    let mut flow = vec![0.0; size * size];
+    let mut rng = hash(region_id.x, region_id.y, seed as u32) | 1;
+    for _ in 0..DROPLETS {
+        droplet(&mut heights, &mut flow, size, &mut rng);
+    }
+
+    let river: Vec<f32> = flow
+        .iter()
+        .map(|flow| ((flow - RIVER_FLOW) / RIVER_FLOW).clamp(0.0, 1.0))
+        .collect();
+    let delta = heights
+        .iter()
+        .zip(original.iter())
+        .zip(river.iter())
+        .map(|((height, original), river)| height - original - river * RIVER_DEPTH)
+        .collect();
+
+    Region {
+        delta,
+        river,
+        last_used: AtomicU64::new(0),
+    }
+}
+
+/// Rolls a single drop of water downhill, picking up sediment where it speeds up and dropping it
+/// where it slows down
+fn droplet(heights: &mut [f32], flow: &mut [f32], size: usize, rng: &mut u32) {
+    let max = (size - 1) as f32;
+    let mut pos = Vec2::new(random(rng) * max, random(rng) * max);
+    let mut dir = Vec2::ZERO;
+    let mut speed = 1.0;
+    let mut water = 1.0;
+    let mut sediment = 0.0;
+
+    for _ in 0..LIFETIME {
+        let cell = Vec2::new(pos.x.floor(), pos.y.floor());
+        let offset = pos - cell;
+        let i = cell.x as usize + cell.y as usize * size;
+        let (height, gradient) = sample(heights, size, pos);
+
+        dir = dir * INERTIA - gradient * (1.0 - INERTIA);
+        if dir.length_squared() < 1e-8 {
+            break;
+        }
+        dir = dir.normalize();
+        flow[i] += water;
+
+        let next = pos + dir;
+        if next.x < 0.0 || next.y < 0.0 || next.x >= max || next.y >= max {
+            break;
+        }
+        let delta_height = sample(heights, size, next).0 - height;
+
+        let capacity = (-delta_height * speed * water * CAPACITY).max(MIN_CAPACITY);
+        let change = if sediment > capacity || delta_height > 0.0 {
+            // Fill the pit it's climbing out of, or drop what it can't carry
+            let deposit = if delta_height > 0.0 {
+                delta_height.min(sediment)
+            } else {
+                (sediment - capacity) * DEPOSIT_SPEED
+            };
+            sediment -= deposit;
+            deposit
+        } else {
+            let erode = ((capacity - sediment) * ERODE_SPEED).min(-delta_height);
+            sediment += erode;
+            -erode
+        };
+
+        heights[i] += change * (1.0 - offset.x) * (1.0 - offset.y);
+        heights[i + 1] += change * offset.x * (1.0 - offset.y);
+        heights[i + size] += change * (1.0 - offset.x) * offset.y;
+        heights[i + size + 1] += change * offset.x * offset.y;
+
+        speed = (speed * speed - delta_height * GRAVITY).max(0.0).sqrt();
+        water *= 1.0 - EVAPORATE_SPEED;
+        pos = next;
+    }
+}
+
+/// Bilinearly interpolated height and gradient, `pos` must be inside the last row and column
+#[inline]
+fn sample(heights: &[f32], size: usize, pos: Vec2) -> (f32, Vec2) {
+    let cell = Vec2::new(pos.x.floor(), pos.y.floor());
+    let (u, v) = (pos.x - cell.x, pos.y - cell.y);
+    let i = cell.x as usize + cell.y as usize * size;
+
+    let nw = heights[i];
+    let ne = heights[i + 1];
+    let sw = heights[i + size];
+    let se = heights[i + size + 1];
+
+    let height = nw * (1.0 - u) * (1.0 - v) + ne * u * (1.0 - v) + sw * (1.0 - u) * v + se * u * v;
+    let gradient = Vec2::new(
+        (ne - nw) * (1.0 - v) + (se - sw) * v,
+        (sw - nw) * (1.0 - u) + (se - ne) * u,
+    );
+    (height, gradient)
+}
+
+/// xorshift, returns 0 to 1
+#[inline]
+fn random(state: &mut u32) -> f32 {
+    *state ^= *state << 13;
+    *state ^= *state >> 17;
+    *state ^= *state << 5;
+    (*state >> 8) as f32 / (1 << 24) as f32
+}
@@ -0,0 +1,97 @@
+use bevy::{app::AppExit, prelude::*};
+
+use super::lifecycle::{ChunkStates, ChunkStatus};
+use super::spawn::{self, AwaitingSpawn};
+use super::stream::{self, ChunkObserver, TaskCounts};
+use super::Character;
+
+/// How long a headless run lasts
+const RUN_SECONDS: f64 = 60.0;
+/// Radians per second the scripted path turns, so it keeps reaching new chunks and also turns
+/// back towards old ones
+const TURN_RATE: f32 = 0.2;
+
+/// Flies the character along a fixed path without a window, printing how fast chunks are
+/// generated and meshed. For benchmarking chunk streaming, or running it where there's no GPU.
+pub struct HeadlessPlugin;
+
+impl Plugin for HeadlessPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(spawn_character.system())
+            .add_system(fly_path.system())
+            .add_system_to_stage(CoreStage::PostUpdate, follow_character.system())
+            .add_system(report.system());
+    }
+}
+
+fn spawn_character(mut commands: Commands) {
+    commands
+        .spawn()
+        .insert(Transform::from_translation(spawn::holding_position()))
+        .insert(Character::waiting_to_spawn())
+        .insert(AwaitingSpawn)
+        .insert(ChunkObserver::default());
+}
+
+/// Flies forwards while slowly turning in a circle
+fn fly_path(mut character: Query<(&mut Transform, &mut Character)>, time: Res<Time>) {
+    if let Ok((mut transform, mut character)) = character.single_mut() {
+        character.rotation.x = time.seconds_since_startup() as f32 * TURN_RATE;
+        transform.rotation = Quat::from_axis_angle(Vec3::Y, character.rotation.x);
+        character.input = Vec3::new(0.0, 0.0, -1.0);
+    }
+}
+
+/// Moves the transform chunk streaming follows along with the character, which the client does
+/// while drawing the camera
+fn follow_character(mut character: Query<(&mut Transform, &Character)>) {
+    if let Ok((mut transform, character)) = character.single_mut() {
+        transform.translation = character.position;
+    }
+}
+
+/// Prints throughput every second, and totals once the run is over
+fn report(
+    time: Res<Time>,
+    states: Res<ChunkStates>,
+    mut last: Local<Option<(f64, TaskCounts)>>,
+    mut exit: EventWriter<AppExit>,
+) {
+    let now = time.seconds_since_startup();
+    let counts = stream::task_counts();
+    let (since, previous) = match &*last {
+        Some((since, previous)) => (*since, previous),
+        None => {
+            *last = Some((now, counts));
+            return;
+        }
+    };
+    let elapsed = now - since;
+    if elapsed < 1.0 && now < RUN_SECONDS {
+        return;
+    }
+
+    println!(
+        "{:.0}s: {:.1} generated/s, {:.1} meshed/s, {} ready, {} cancelled, {} wasted",
+        now,
+        (counts.generated - previous.generated) as f64 / elapsed,
+        (counts.meshed - previous.meshed) as f64 / elapsed,
+        states.count(ChunkStatus::Ready),
+        counts.cancelled,
+        counts.wasted
+    );
+
+    if now >= RUN_SECONDS {
+        println!(
+            "Generated {} chunks ({:.1}/s), decorated {}, meshed {} ({:.1}/s) in {:.0}s",
+            counts.generated,
+            counts.generated as f64 / now,
+            counts.decorated,
+            counts.meshed,
+            counts.meshed as f64 / now,
+            now
+        );
+        exit.send(AppExit);
+    }
+    *last = Some((now, counts));
+}
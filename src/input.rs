@@ -0,0 +1,95 @@
+use bevy::{
+    ecs::system::SystemParam,
+    input::gamepad::{GamepadEvent, GamepadEventType},
+    prelude::*,
+};
+
+use super::settings::{Action, Binding, Settings};
+
+/// The gamepad that drives the character, the one connected most recently
+#[derive(Default)]
+pub struct ActiveGamepad(pub Option<Gamepad>);
+
+pub fn track_gamepads(
+    mut gamepad_events: EventReader<GamepadEvent>,
+    mut active: ResMut<ActiveGamepad>,
+) {
+    for GamepadEvent(gamepad, event) in gamepad_events.iter() {
+        match event {
+            GamepadEventType::Connected => active.0 = Some(*gamepad),
+            GamepadEventType::Disconnected if active.0 == Some(*gamepad) => active.0 = None,
+            _ => {}
+        }
+    }
+}
+
+/// Keyboard, mouse and gamepad state read through the bindings in `Settings`
+#[derive(SystemParam)]
+pub struct Actions<'a> {
+    settings: Res<'a, Settings>,
+    keys: Res<'a, Input<KeyCode>>,
+    mouse_buttons: Res<'a, Input<MouseButton>>,
+    gamepad_buttons: Res<'a, Input<GamepadButton>>,
+    gamepad_axes: Res<'a, Axis<GamepadAxis>>,
+    gamepad: Res<'a, ActiveGamepad>,
+}
+
+impl<'a> Actions<'a> {
+    pub fn pressed(&self, action: Action) -> bool {
+        self.settings
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.keys.pressed(key),
+                Binding::Mouse(button) => self.mouse_buttons.pressed(button),
+                Binding::Gamepad(button) => self.gamepad.0.map_or(false, |gamepad| {
+                    self.gamepad_buttons.pressed(GamepadButton(gamepad, button))
+                }),
+            })
+    }
+
+    pub fn just_pressed(&self, action: Action) -> bool {
+        self.settings
+            .bindings(action)
+            .iter()
+            .any(|binding| match *binding {
+                Binding::Key(key) => self.keys.just_pressed(key),
+                Binding::Mouse(button) => self.mouse_buttons.just_pressed(button),
+                Binding::Gamepad(button) => self.gamepad.0.map_or(false, |gamepad| {
+                    self.gamepad_buttons
+                        .just_pressed(GamepadButton(gamepad, button))
+                }),
+            })
+    }
+
+    /// Strafe and forward from the left stick
+    pub fn move_stick(&self) -> Vec2 {
+        self.stick(GamepadAxisType::LeftStickX, GamepadAxisType::LeftStickY)
+    }
+
+    /// Turn and tilt from the right stick
+    pub fn look_stick(&self) -> Vec2 {
+        self.stick(GamepadAxisType::RightStickX, GamepadAxisType::RightStickY)
+    }
+
+    /// Both axes of a stick with a round dead zone, scaled so it still goes from 0 to 1 outside it
+    fn stick(&self, x: GamepadAxisType, y: GamepadAxisType) -> Vec2 {
+        let gamepad = match self.gamepad.0 {
+            Some(gamepad) => gamepad,
+            None => return Vec2::ZERO,
+        };
+        let axis = |axis| {
+            self.gamepad_axes
+                .get(GamepadAxis(gamepad, axis))
+                .unwrap_or(0.0)
+        };
+
+        let stick = Vec2::new(axis(x), axis(y));
+        let length = stick.length();
+        let dead_zone = self.settings.dead_zone;
+        if length <= dead_zone {
+            return Vec2::ZERO;
+        }
+        stick / length * ((length - dead_zone) / (1.0 - dead_zone)).min(1.0)
+    }
+}
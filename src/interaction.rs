@@ -0,0 +1,138 @@
+use bevy::prelude::*;
+use std::sync::Arc;
+
+use super::block::*;
+use super::input::Actions;
+use super::physics::{self, Aabb};
+use super::settings::Action;
+use super::world::World;
+use super::Character;
+
+/// How far away blocks can be broken or placed
+const REACH: f32 = 5.0;
+
+/// Blocks that can be placed, cycled with `HotbarNext` and `HotbarPrevious`
+pub struct Hotbar {
+    pub slots: [u16; 9],
+    pub selected: usize,
+}
+
+impl Default for Hotbar {
+    fn default() -> Self {
+        Hotbar {
+            slots: [
+                STONE,
+                DIRT,
+                GRASS,
+                PLANKS,
+                COBBLESTONE,
+                LOG,
+                GLOWSTONE,
+                TORCH,
+                WATER,
+            ],
+            selected: 0,
+        }
+    }
+}
+
+/// Text in the corner naming the selected block
+pub struct HotbarText;
+
+pub fn setup_hotbar(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands
+        .spawn_bundle(TextBundle {
+            text: Text {
+                sections: vec![TextSection {
+                    value: String::new(),
+                    style: TextStyle {
+                        font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                        font_size: 30.0,
+                        color: Color::rgb(0.0, 0.0, 0.0),
+                    },
+                }],
+                ..Default::default()
+            },
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(10.0),
+                    left: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(HotbarText);
+}
+
+pub fn show_selected(hotbar: Res<Hotbar>, mut text: Query<&mut Text, With<HotbarText>>) {
+    if !hotbar.is_changed() {
+        return;
+    }
+    for mut text in text.iter_mut() {
+        text.sections[0].value = BLOCKS[hotbar.slots[hotbar.selected] as usize]
+            .name
+            .to_string();
+    }
+}
+
+/// Breaks and places blocks where the camera is looking
+pub fn interaction_system(
+    character: Query<(&Transform, &Character)>,
+    actions: Actions,
+    windows: Res<Windows>,
+    world: Res<Arc<World>>,
+    mut hotbar: ResMut<Hotbar>,
+) {
+    if !windows
+        .get_primary()
+        .map_or(false, |window| window.cursor_locked())
+    {
+        return;
+    }
+
+    let slots = hotbar.slots.len();
+    if actions.just_pressed(Action::HotbarNext) {
+        hotbar.selected = (hotbar.selected + 1) % slots;
+    }
+    if actions.just_pressed(Action::HotbarPrevious) {
+        hotbar.selected = (hotbar.selected + slots - 1) % slots;
+    }
+
+    let (transform, character) = match character.single() {
+        Ok(character) => character,
+        Err(_) => return,
+    };
+    let breaking = actions.just_pressed(Action::Break);
+    let placing = actions.just_pressed(Action::Place);
+    if !breaking && !placing {
+        return;
+    }
+
+    let hit = match physics::raycast(&world, transform.translation, -transform.local_z(), REACH) {
+        Some(hit) => hit,
+        None => return,
+    };
+    if breaking {
+        world.set_block(hit.block, AIR);
+    } else {
+        let target = hit.block + hit.normal;
+        let replaceable = world
+            .get_block(target)
+            .map_or(false, |block| block == AIR || is_fluid(block));
+        let block = hotbar.slots[hotbar.selected];
+
+        // Solid blocks can't be placed where they'd trap the character
+        let feet = character.position - Vec3::new(0.0, physics::EYE_HEIGHT, 0.0);
+        let player = Aabb::player(feet);
+        let target_min = Vec3::new(target.x as f32, target.y as f32, target.z as f32);
+        let overlaps =
+            player.min.cmplt(target_min + Vec3::ONE).all() && player.max.cmpgt(target_min).all();
+
+        if replaceable && !(BLOCKS[block as usize].solid && overlaps) {
+            world.set_block(target, block);
+        }
+    }
+}
@@ -0,0 +1,503 @@
+//! World generation, lighting and meshing, plus the plugins the game is built from
+#![allow(non_upper_case_globals)]
+
+// Imports
+use bevy::{
+    core::{FixedTimestep, FixedTimesteps},
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    input::mouse::MouseMotion,
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        camera::PerspectiveProjection,
+        pipeline::PipelineDescriptor,
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{ShaderStage, ShaderStages},
+    },
+    transform::TransformSystem,
+};
+use std::sync::Arc;
+
+pub mod block;
+mod cache;
+pub mod chunk;
+mod console;
+pub mod coords;
+mod daytime;
+pub mod decoration;
+pub mod erosion;
+mod headless;
+mod input;
+mod interaction;
+mod lifecycle;
+pub mod light;
+mod physics;
+pub mod preset;
+mod settings;
+mod sky;
+mod spawn;
+mod stream;
+pub mod world;
+pub use daytime::WorldTime;
+pub use headless::HeadlessPlugin;
+pub use settings::{Action, Binding, Settings};
+pub use stream::{ChunkObserver, ChunkStreamPlugin};
+
+use chunk::*;
+use console::Console;
+use input::{Actions, ActiveGamepad};
+use interaction::{Hotbar, HotbarText};
+use physics::Aabb;
+use sky::{SkyMaterial, Underwater};
+use spawn::AwaitingSpawn;
+use world::World;
+
+/// Rate of the simulation stage, which moves the character and advances world time
+const TICKS_PER_SECOND: f64 = 20.0;
+const TICK_SECONDS: f32 = (1.0 / TICKS_PER_SECOND) as f32;
+const SIMULATION: &str = "simulation";
+const SIMULATION_TIMESTEP: &str = "simulation_timestep";
+
+// Structs
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "0320b9b8-b3a3-4baa-8bfa-c94008177b17"]
+struct ChunkMaterial {
+    texture_atlas: Handle<Texture>,
+    /// Towards the sun or moon, set from `WorldTime`
+    light_direction: Vec3,
+    /// Brightness of full sky light, set from `WorldTime`
+    sky_light: f32,
+    /// Horizon colour, set from `WorldTime`
+    fog_colour: Color,
+    fog_start: f32,
+    fog_end: f32,
+}
+
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "93fb26fc-6c05-489b-9029-601edf703b6b"]
+struct TextureAtlas {
+    texture: Handle<Texture>,
+}
+
+struct ChunkMaterialHandle(Handle<ChunkMaterial>);
+struct ChunkPipelineHandle(Handle<PipelineDescriptor>);
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum MovementMode {
+    /// Noclip flight
+    Flying,
+    /// Falls and collides with solid blocks
+    Walking,
+}
+
+struct Character {
+    velocity: Vec3,
+    rotation: Vec2,
+    mode: MovementMode,
+    on_ground: bool,
+    /// Eye position as of the last tick, the camera is drawn between this and `previous_position`
+    position: Vec3,
+    previous_position: Vec3,
+    /// Held movement keys as strafe, up and backwards, read every frame and used every tick
+    input: Vec3,
+}
+
+impl Default for Character {
+    fn default() -> Self {
+        Self {
+            velocity: Vec3::new(0.0, 0.0, 0.0),
+            rotation: Vec2::new(0.0, 0.0),
+            mode: MovementMode::Flying,
+            on_ground: false,
+            position: Vec3::ZERO,
+            previous_position: Vec3::ZERO,
+            input: Vec3::ZERO,
+        }
+    }
+}
+
+impl Character {
+    /// Held where the chunks around the origin load, until `AwaitingSpawn` is taken off
+    fn waiting_to_spawn() -> Self {
+        let position = spawn::holding_position();
+        Character {
+            position,
+            previous_position: position,
+            ..Default::default()
+        }
+    }
+}
+
+const neighbors: [[i32; 2]; 9] = [
+    [0, 0],
+    [-1, -1],
+    [0, -1],
+    [1, -1],
+    [-1, 0],
+    [1, 0],
+    [-1, 1],
+    [0, 1],
+    [1, 1],
+];
+
+// Functions
+/// Moves the character and advances world time on a fixed tick
+pub struct SimulationPlugin;
+
+impl Plugin for SimulationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system(spawn::place_at_spawn.system())
+            .add_stage_after(
+                CoreStage::Update,
+                SIMULATION,
+                SystemStage::parallel()
+                    .with_run_criteria(
+                        FixedTimestep::steps_per_second(TICKS_PER_SECOND)
+                            .with_label(SIMULATION_TIMESTEP),
+                    )
+                    .with_system(character_tick_system.system())
+                    .with_system(daytime::advance_time.system()),
+            );
+    }
+}
+
+/// The window, camera, input, sky and everything else only needed to play
+pub struct ClientPlugin;
+
+impl Plugin for ClientPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(Underwater::default())
+            .insert_resource(ActiveGamepad::default())
+            .insert_resource(Hotbar::default())
+            .insert_resource(Console::spawn())
+            .add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_asset::<ChunkMaterial>()
+            .add_asset::<SkyMaterial>()
+            .add_startup_system(setup.system())
+            .add_startup_system(sky::setup_sky.system())
+            .add_startup_system(character_setup.system())
+            .add_startup_system(interaction::setup_hotbar.system())
+            .add_system(input::track_gamepads.system())
+            .add_system(character_input_system.system())
+            .add_system(interaction::interaction_system.system())
+            .add_system(interaction::show_selected.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                interpolate_camera
+                    .system()
+                    .label("interpolate_camera")
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_system(fps_system.system())
+            .add_system(sky::detect_underwater.system())
+            .add_system(daytime::apply_time.system())
+            .add_system_to_stage(
+                CoreStage::PostUpdate,
+                sky::follow_camera
+                    .system()
+                    .after("interpolate_camera")
+                    .before(TransformSystem::TransformPropagate),
+            )
+            .add_system(console::console_system.system())
+            .add_system(settings::apply_fov.system())
+            .add_system(settings::save_settings.system());
+    }
+}
+
+fn setup(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut pbr_materials: ResMut<Assets<StandardMaterial>>,
+    mut chunk_materials: ResMut<Assets<ChunkMaterial>>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut render_graph: ResMut<RenderGraph>,
+    asset_server: Res<AssetServer>,
+    settings: Res<Settings>,
+) {
+    // custom pipeline
+    let texture_atlas_handle = asset_server.load("textures/terrain.png");
+
+    let pipeline_handle = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("chunk.vert"),
+        )),
+        fragment: Some(shaders.add(Shader::from_glsl(
+            ShaderStage::Fragment,
+            include_str!("chunk.frag"),
+        ))),
+    }));
+    commands.insert_resource(ChunkPipelineHandle(pipeline_handle));
+
+    render_graph.add_system_node(
+        "chunk_material",
+        AssetRenderResourcesNode::<ChunkMaterial>::new(true),
+    );
+
+    render_graph
+        .add_node_edge("chunk_material", base::node::MAIN_PASS)
+        .unwrap();
+
+    let (fog_start, fog_end) = sky::fog_range(settings.view_distance);
+    let chunk_material_handle = chunk_materials.add(ChunkMaterial {
+        texture_atlas: texture_atlas_handle,
+        light_direction: Vec3::Y,
+        sky_light: 1.0,
+        fog_colour: Color::WHITE,
+        fog_start,
+        fog_end,
+    });
+    commands.insert_resource(ChunkMaterialHandle(chunk_material_handle));
+
+    // camera
+    commands
+        .spawn_bundle(PerspectiveCameraBundle {
+            transform: Transform::from_translation(spawn::holding_position()),
+            perspective_projection: PerspectiveProjection {
+                fov: settings.fov.to_radians(),
+                near: 0.05,
+                far: 10000.0,
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(Character::waiting_to_spawn())
+        .insert(AwaitingSpawn)
+        .insert(ChunkObserver::default());
+
+    // origin
+    commands.spawn_bundle(PbrBundle {
+        mesh: meshes.add(Mesh::from(shape::Cube { size: 0.1 })),
+        material: pbr_materials.add(bevy::prelude::Color::rgb(0.1, 0.1, 0.1).into()),
+        transform: Transform {
+            scale: Vec3::new(1.0, 10000.0, 1.0),
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+
+    // ui
+    commands.spawn_bundle(UiCameraBundle::default());
+
+    // fps
+    commands.spawn_bundle(TextBundle {
+        text: Text {
+            sections: vec![TextSection {
+                value: "0.00".to_string(),
+                style: TextStyle {
+                    font: asset_server.load("fonts/FiraSans-Bold.ttf"),
+                    font_size: 40.0,
+                    color: Color::rgb(0.0, 0.0, 0.0),
+                    ..Default::default()
+                },
+            }],
+            ..Default::default()
+        },
+        style: Style {
+            position_type: PositionType::Absolute,
+            position: Rect {
+                top: Val::Px(10.0),
+                left: Val::Px(10.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        ..Default::default()
+    });
+}
+
+fn fps_system(diagnostics: Res<Diagnostics>, mut query: Query<&mut Text, Without<HotbarText>>) {
+    if let Some(fps) = diagnostics.get(FrameTimeDiagnosticsPlugin::FPS) {
+        if let Some(average) = fps.average() {
+            for mut text in query.iter_mut() {
+                text.sections[0].value = format!("{:.1}", average);
+            }
+        }
+    }
+}
+
+/// Grabs/ungrabs mouse cursor
+fn toggle_grab_cursor(window: &mut Window) {
+    window.set_cursor_lock_mode(!window.cursor_locked());
+    window.set_cursor_visibility(!window.cursor_visible());
+}
+
+fn character_setup(mut windows: ResMut<Windows>) {
+    toggle_grab_cursor(windows.get_primary_mut().unwrap());
+}
+
+/// Reads input every frame. Looking around happens straight away, movement on the next tick.
+fn character_input_system(
+    mut character: Query<(&mut Transform, &mut Character)>,
+    actions: Actions,
+    mut mouse_motion_events: EventReader<MouseMotion>,
+    mut windows: ResMut<Windows>,
+    settings: Res<Settings>,
+    time: Res<Time>,
+) {
+    let window = windows.get_primary_mut().unwrap();
+    if actions.just_pressed(Action::ReleaseCursor) {
+        toggle_grab_cursor(window);
+    }
+
+    if let Ok((mut transform, mut character)) = character.single_mut() {
+        if !window.cursor_locked() {
+            character.input = Vec3::ZERO;
+            return;
+        }
+
+        if actions.just_pressed(Action::ToggleFly) {
+            character.mode = match character.mode {
+                MovementMode::Flying => MovementMode::Walking,
+                MovementMode::Walking => MovementMode::Flying,
+            };
+            character.velocity = Vec3::ZERO;
+            character.on_ground = false;
+        }
+
+        // movement
+        let stick = actions.move_stick();
+        let input = Vec3::new(
+            (actions.pressed(Action::Right) as i32 - actions.pressed(Action::Left) as i32) as f32
+                + stick.x,
+            (actions.pressed(Action::Jump) as i32 - actions.pressed(Action::Descend) as i32) as f32,
+            (actions.pressed(Action::Back) as i32 - actions.pressed(Action::Forward) as i32) as f32
+                - stick.y,
+        );
+        character.input = input.clamp(Vec3::splat(-1.0), Vec3::ONE);
+        // rotation
+        let mut mouse_delta = Vec2::new(0.0, 0.0);
+        for event in mouse_motion_events.iter() {
+            mouse_delta += event.delta;
+        }
+        let look = actions.look_stick();
+        if mouse_delta != Vec2::ZERO || look != Vec2::ZERO {
+            let sensitivity = settings.sensitivity;
+            character.rotation -= mouse_delta * sensitivity;
+            // Pushing the stick up looks up, unlike moving the mouse up
+            character.rotation +=
+                Vec2::new(-look.x, look.y) * settings.gamepad_sensitivity * time.delta_seconds();
+            character.rotation.y = character.rotation.y.clamp(-1.54, 1.54);
+            // Order is important to prevent unintended roll
+            transform.rotation = Quat::from_axis_angle(Vec3::Y, character.rotation.x)
+                * Quat::from_axis_angle(Vec3::X, character.rotation.y);
+        }
+    }
+}
+
+/// Moves the character by one tick
+fn character_tick_system(
+    mut character: Query<(&Transform, &mut Character), Without<AwaitingSpawn>>,
+    world: Res<Arc<World>>,
+    settings: Res<Settings>,
+) {
+    if let Ok((transform, mut character)) = character.single_mut() {
+        character.previous_position = character.position;
+
+        match character.mode {
+            MovementMode::Flying => {
+                // Like walking, sticks can ask for less than full speed but diagonals are never
+                // faster
+                let mut input = character.input;
+                if input.length_squared() > 1.0 {
+                    input = input.normalize();
+                }
+                input *= settings.fly_speed;
+                let target_velocity = input.z * transform.local_z()
+                    + input.x * transform.local_x()
+                    + input.y * transform.local_y();
+                character.velocity = character.velocity
+                    + (target_velocity - character.velocity)
+                        * (1.0 - 0.9f32.powf(TICK_SECONDS * 120.0));
+                let velocity = character.velocity;
+                character.position += velocity * TICK_SECONDS;
+            }
+            MovementMode::Walking => {
+                let input = Vec2::new(character.input.x, -character.input.z);
+                let jump = character.input.y > 0.0;
+                walk(&world, &mut character, input, jump, TICK_SECONDS);
+            }
+        }
+    }
+}
+
+/// Draws the camera part way between the last two ticks, so movement is smooth at any frame rate
+fn interpolate_camera(
+    mut character: Query<(&mut Transform, &Character)>,
+    fixed_timesteps: Res<FixedTimesteps>,
+) {
+    let overstep = fixed_timesteps
+        .get(SIMULATION_TIMESTEP)
+        .map_or(1.0, |timestep| timestep.overstep_percentage() as f32);
+
+    if let Ok((mut transform, character)) = character.single_mut() {
+        transform.translation = character
+            .previous_position
+            .lerp(character.position, overstep.min(1.0));
+    }
+}
+
+/// One step of walking physics. `input` is strafe and forward, relative to where the character
+/// is facing.
+fn walk(world: &World, character: &mut Character, input: Vec2, jump: bool, delta_time: f32) {
+    let feet = character.position - Vec3::new(0.0, physics::EYE_HEIGHT, 0.0);
+    let eyes_in_fluid = physics::in_fluid(world, character.position);
+    let swimming = eyes_in_fluid || physics::in_fluid(world, feet);
+
+    let yaw = character.rotation.x;
+    let forward = Vec3::new(-yaw.sin(), 0.0, -yaw.cos());
+    let right = Vec3::new(yaw.cos(), 0.0, -yaw.sin());
+    // Sticks can ask for less than full speed, but diagonals are never faster
+    let input = if input.length_squared() > 1.0 {
+        input.normalize()
+    } else {
+        input
+    };
+    let speed = if swimming {
+        physics::SWIM_SPEED
+    } else {
+        physics::WALK_SPEED
+    };
+    let target_velocity = (right * input.x + forward * input.y) * speed;
+
+    // Much less control in the air
+    let control = if character.on_ground || swimming {
+        1.0 - 0.9f32.powf(delta_time * 120.0)
+    } else {
+        1.0 - 0.9f32.powf(delta_time * 20.0)
+    };
+    character.velocity.x += (target_velocity.x - character.velocity.x) * control;
+    character.velocity.z += (target_velocity.z - character.velocity.z) * control;
+
+    if swimming {
+        let buoyancy = if eyes_in_fluid {
+            physics::BUOYANCY
+        } else {
+            0.0
+        };
+        character.velocity.y += (buoyancy - physics::FLUID_GRAVITY) * delta_time;
+        if jump {
+            character.velocity.y = (character.velocity.y
+                + physics::SWIM_UP_ACCELERATION * delta_time)
+                .min(physics::SWIM_UP_SPEED);
+        }
+        character.velocity.y *= physics::FLUID_DRAG.powf(delta_time);
+    } else {
+        if character.on_ground && jump {
+            character.velocity.y = physics::JUMP_VELOCITY;
+        }
+        character.velocity.y =
+            (character.velocity.y - physics::GRAVITY * delta_time).max(-physics::TERMINAL_VELOCITY);
+    }
+
+    let movement = physics::sweep(world, &Aabb::player(feet), character.velocity * delta_time);
+    character.position += movement.moved;
+    for (axis, blocked) in movement.blocked.iter().enumerate() {
+        if *blocked {
+            character.velocity[axis] = 0.0;
+        }
+    }
+    character.on_ground = movement.on_ground;
+}
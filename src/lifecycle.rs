@@ -0,0 +1,216 @@
+use bevy::prelude::{Entity, IVec2};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+};
+
+use super::neighbors;
+
+/// Decoration, lighting and meshing each wait for all eight neighbours to reach the stage before,
+/// so a chunk only meshes once everything this many chunks around it has loaded
+pub const NEIGHBOR_STAGES: usize = 3;
+
+/// Where a chunk is in its lifecycle, in the order chunks normally move through them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ChunkStatus {
+    /// In range and waiting for a generation task
+    Queued,
+    Generating,
+    /// Has terrain, decorated once all of its neighbours have terrain too
+    Terrain,
+    Decorated,
+    Lit,
+    Meshing,
+    Ready,
+    /// Out of range, removed as soon as its running task finishes
+    Unloading,
+}
+
+impl ChunkStatus {
+    pub fn can_become(self, next: ChunkStatus) -> bool {
+        use ChunkStatus::*;
+
+        match (self, next) {
+            (Unloading, _) => false,
+            (_, Unloading) => true,
+            (Queued, Generating)
+            | (Generating, Terrain)
+            | (Terrain, Decorated)
+            | (Decorated, Lit)
+            | (Lit, Meshing)
+            | (Meshing, Ready)
+            | (Ready, Meshing) => true,
+            // Restored from the cache, with its features but without light
+            (Queued, Decorated) => true,
+            // A neighbour was unloaded, so the mesh waits for it to come back
+            (Meshing, Lit) | (Ready, Lit) => true,
+            _ => false,
+        }
+    }
+
+    /// Whether the chunk has got at least as far as `status` and isn't on its way out
+    #[inline]
+    pub fn reached(self, status: ChunkStatus) -> bool {
+        self >= status && self != ChunkStatus::Unloading
+    }
+}
+
+/// Shared with a chunk's tasks so they can stop early once it's unloaded
+#[derive(Clone, Default)]
+pub struct CancelToken(Arc<AtomicBool>);
+
+impl CancelToken {
+    pub fn cancel(&self) {
+        self.0.store(true, Ordering::Relaxed);
+    }
+
+    #[inline]
+    pub fn is_cancelled(&self) -> bool {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+pub struct ChunkState {
+    pub status: ChunkStatus,
+    /// A decoration or lighting task is running, neither of which has a status of its own
+    pub busy: bool,
+    pub mesh: Option<Entity>,
+    /// Cancelled when the chunk leaves range
+    pub cancel: CancelToken,
+}
+
+impl ChunkState {
+    /// Whether any task is working on the chunk's data
+    pub fn in_flight(&self) -> bool {
+        self.busy || matches!(self.status, ChunkStatus::Generating | ChunkStatus::Meshing)
+    }
+}
+
+/// The single source of truth for which chunks exist and what they're waiting on. Only touched
+/// from the main thread, tasks report back through `ChunkJobs`.
+#[derive(Default)]
+pub struct ChunkStates {
+    states: HashMap<IVec2, ChunkState>,
+    /// Queued chunks in the order they should be generated
+    queue: VecDeque<IVec2>,
+    /// Chunks whose own or neighbours' status changed since they were last looked at
+    dirty: HashSet<IVec2>,
+}
+
+impl ChunkStates {
+    #[inline]
+    pub fn get(&self, chunk_id: IVec2) -> Option<&ChunkState> {
+        self.states.get(&chunk_id)
+    }
+
+    #[inline]
+    pub fn get_mut(&mut self, chunk_id: IVec2) -> Option<&mut ChunkState> {
+        self.states.get_mut(&chunk_id)
+    }
+
+    #[inline]
+    pub fn status(&self, chunk_id: IVec2) -> Option<ChunkStatus> {
+        self.states.get(&chunk_id).map(|state| state.status)
+    }
+
+    /// Panics if the chunk isn't tracked
+    pub fn cancel_token(&self, chunk_id: IVec2) -> CancelToken {
+        self.states[&chunk_id].cancel.clone()
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = IVec2> + '_ {
+        self.states.keys().copied()
+    }
+
+    /// Starts tracking a chunk as `Queued`
+    pub fn queue(&mut self, chunk_id: IVec2) {
+        let previous = self.states.insert(
+            chunk_id,
+            ChunkState {
+                status: ChunkStatus::Queued,
+                busy: false,
+                mesh: None,
+                cancel: CancelToken::default(),
+            },
+        );
+        assert!(previous.is_none(), "Queued chunk {} twice", chunk_id);
+        self.queue.push_back(chunk_id);
+    }
+
+    pub fn pop_queued(&mut self) -> Option<IVec2> {
+        self.queue.pop_front()
+    }
+
+    pub fn queued_len(&self) -> usize {
+        self.queue.len()
+    }
+
+    /// Sorts the queue so chunks with the lowest `priority` come out first, dropping chunks that
+    /// have been unloaded since they were queued
+    pub fn prioritise_queue(&mut self, priority: impl Fn(IVec2) -> f32) {
+        let states = &self.states;
+        let mut queue: Vec<(f32, IVec2)> = self
+            .queue
+            .drain(..)
+            .filter(|chunk_id| {
+                states.get(chunk_id).map(|state| state.status) == Some(ChunkStatus::Queued)
+            })
+            .map(|chunk_id| (priority(chunk_id), chunk_id))
+            .collect();
+        queue.sort_by(|a, b| a.0.partial_cmp(&b.0).unwrap_or(std::cmp::Ordering::Equal));
+        self.queue = queue.into_iter().map(|(_, chunk_id)| chunk_id).collect();
+    }
+
+    /// How many tracked chunks are in `status`
+    pub fn count(&self, status: ChunkStatus) -> usize {
+        self.states
+            .values()
+            .filter(|state| state.status == status)
+            .count()
+    }
+
+    pub fn transition(&mut self, chunk_id: IVec2, next: ChunkStatus) {
+        let state = self
+            .states
+            .get_mut(&chunk_id)
+            .expect("Tried to transition a chunk that isn't tracked");
+        assert!(
+            state.status.can_become(next),
+            "Invalid chunk transition {:?} -> {:?} for {}",
+            state.status,
+            next,
+            chunk_id
+        );
+        state.status = next;
+        self.mark_dirty(chunk_id);
+    }
+
+    pub fn remove(&mut self, chunk_id: IVec2) -> Option<ChunkState> {
+        let state = self.states.remove(&chunk_id);
+        if state.is_some() {
+            self.mark_dirty(chunk_id);
+        }
+        state
+    }
+
+    /// Whether the chunk and all eight of its neighbours have reached `status`
+    pub fn neighbors_reached(&self, chunk_id: IVec2, status: ChunkStatus) -> bool {
+        neighbors.iter().all(|dir| {
+            self.status(chunk_id + IVec2::from(*dir))
+                .map_or(false, |neighbor| neighbor.reached(status))
+        })
+    }
+
+    pub fn take_dirty(&mut self) -> HashSet<IVec2> {
+        std::mem::take(&mut self.dirty)
+    }
+
+    fn mark_dirty(&mut self, chunk_id: IVec2) {
+        for dir in neighbors {
+            self.dirty.insert(chunk_id + IVec2::from(dir));
+        }
+    }
+}
@@ -0,0 +1,347 @@
+use bevy::prelude::{IVec2, IVec3};
+use std::collections::{HashMap, HashSet, VecDeque};
+
+use super::block::{emission, is_opaque};
+use super::chunk::*;
+use super::coords::{BlockPos, ChunkPos, LocalPos};
+use super::world::World;
+
+pub const MAX_LIGHT: u8 = 15;
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LightChannel {
+    Sky,
+    Block,
+}
+
+const LIGHT_CHANNELS: [LightChannel; 2] = [LightChannel::Sky, LightChannel::Block];
+const DIRECTIONS: [[i32; 3]; 6] = [
+    [0, 0, 1],
+    [0, 0, -1],
+    [1, 0, 0],
+    [-1, 0, 0],
+    [0, 1, 0],
+    [0, -1, 0],
+];
+const DOWN: usize = 5;
+
+/// Light offered to cells of other chunks, by chunk and local position
+type Pending = HashMap<IVec2, Vec<(IVec3, u8)>>;
+
+/// Level that light moving in `direction` arrives with. Full sky light goes straight down without
+/// fading, everything else loses one level per block.
+#[inline]
+fn falloff(channel: LightChannel, direction: usize, level: u8) -> u8 {
+    if channel == LightChannel::Sky && direction == DOWN && level == MAX_LIGHT {
+        MAX_LIGHT
+    } else {
+        level.saturating_sub(1)
+    }
+}
+
+/// Initial lighting for a chunk whose neighbours have all been decorated. Light is only ever
+/// raised, so neighbours lighting at the same time end up with the same result in any order.
+pub fn light_chunk(world: &World, chunk_id: IVec2) {
+    for channel in LIGHT_CHANNELS {
+        let mut pending = Pending::new();
+
+        // Light already in the neighbours has to come back in, as this chunk may have been
+        // regenerated after they were lit
+        let mut offers = Vec::new();
+        for (direction, dir) in DIRECTIONS.iter().enumerate().take(4) {
+            let dir = IVec3::from(*dir);
+            let neighbor_id = chunk_id - IVec2::new(dir.x, dir.z);
+            if let Some(neighbor) = world.chunks.get(&neighbor_id) {
+                for (inside, outside) in border(dir) {
+                    let level = falloff(
+                        channel,
+                        direction,
+                        neighbor.light(
+                            channel,
+                            outside.x as usize,
+                            outside.y as usize,
+                            outside.z as usize,
+                        ),
+                    );
+                    if level > 0 {
+                        offers.push((inside, level));
+                    }
+                }
+            }
+        }
+        pending.insert(chunk_id, offers);
+
+        if let Some(mut chunk) = world.chunks.get_mut(&chunk_id) {
+            let mut queue = VecDeque::new();
+            match channel {
+                LightChannel::Sky => seed_sky(&mut chunk, &mut queue),
+                LightChannel::Block => seed_emitters(&mut chunk, &mut queue),
+            }
+            flood(&mut chunk, chunk_id, channel, &mut queue, &mut pending);
+        }
+
+        spread(world, channel, pending, &mut HashSet::new());
+    }
+}
+
+/// Relights around a block that was just changed. Returns the chunks whose light changed.
+pub fn block_changed(world: &World, pos: IVec3) -> HashSet<IVec2> {
+    let mut touched = HashSet::new();
+
+    for channel in LIGHT_CHANNELS {
+        // Take away everything that came through the changed block, then fill back in from the
+        // light around the hole
+        let mut removal = VecDeque::new();
+        let mut refill = Vec::new();
+        let level = get_light(world, channel, pos).unwrap_or(0);
+        set_light(world, channel, pos, 0, &mut touched);
+        removal.push_back((pos, level));
+
+        while let Some((pos, level)) = removal.pop_front() {
+            for (direction, dir) in DIRECTIONS.iter().enumerate() {
+                let neighbor = pos + IVec3::from(*dir);
+                let neighbor_level = match get_light(world, channel, neighbor) {
+                    Some(neighbor_level) if neighbor_level > 0 => neighbor_level,
+                    _ => continue,
+                };
+
+                if neighbor_level < level
+                    || (neighbor_level == MAX_LIGHT && falloff(channel, direction, level) == level)
+                {
+                    set_light(world, channel, neighbor, 0, &mut touched);
+                    removal.push_back((neighbor, neighbor_level));
+                    // Emitters caught in the removal light themselves straight back up
+                    if channel == LightChannel::Block {
+                        relight_emitter(world, neighbor, &mut refill, &mut touched);
+                    }
+                } else {
+                    refill.push(neighbor);
+                }
+            }
+        }
+
+        if channel == LightChannel::Block {
+            relight_emitter(world, pos, &mut refill, &mut touched);
+        }
+        if channel == LightChannel::Sky
+            && pos.y == CHUNK_SIZE_Y as i32 - 1
+            && world
+                .get_block(pos)
+                .map_or(false, |block| !is_opaque(block))
+        {
+            set_light(world, channel, pos, MAX_LIGHT, &mut touched);
+            refill.push(pos);
+        }
+
+        // Light from the remaining sources, grouped into the chunks it has to spread through
+        let mut pending = Pending::new();
+        for pos in refill {
+            let level = match get_light(world, channel, pos) {
+                Some(level) if level > 0 => level,
+                _ => continue,
+            };
+            for (direction, dir) in DIRECTIONS.iter().enumerate() {
+                let (ChunkPos(chunk_id), LocalPos(local)) =
+                    BlockPos(pos + IVec3::from(*dir)).split();
+                if local.y >= 0 && local.y < CHUNK_SIZE_Y as i32 {
+                    pending
+                        .entry(chunk_id)
+                        .or_insert_with(Vec::new)
+                        .push((local, falloff(channel, direction, level)));
+                }
+            }
+        }
+        spread(world, channel, pending, &mut touched);
+    }
+
+    touched
+}
+
+/// Works through light offered to other chunks one chunk at a time, so only a single chunk is
+/// ever locked
+fn spread(
+    world: &World,
+    channel: LightChannel,
+    mut pending: Pending,
+    touched: &mut HashSet<IVec2>,
+) {
+    while let Some(chunk_id) = pending.keys().next().copied() {
+        let offers = pending.remove(&chunk_id).unwrap();
+        // Chunks that aren't loaded get their light from their neighbours when they're lit
+        if let Some(mut chunk) = world.chunks.get_mut(&chunk_id) {
+            let mut queue = VecDeque::new();
+            for (pos, level) in offers {
+                let (x, y, z) = (pos.x as usize, pos.y as usize, pos.z as usize);
+                if level > chunk.light(channel, x, y, z) && !is_opaque(chunk.values[x][y][z]) {
+                    chunk.set_light(channel, x, y, z, level);
+                    queue.push_back(pos);
+                }
+            }
+
+            if !queue.is_empty() {
+                touched.insert(chunk_id);
+                flood(&mut chunk, chunk_id, channel, &mut queue, &mut pending);
+            }
+        }
+    }
+}
+
+/// Breadth first spread of the lit cells in `queue` through one chunk
+fn flood(
+    chunk: &mut Chunk,
+    chunk_id: IVec2,
+    channel: LightChannel,
+    queue: &mut VecDeque<IVec3>,
+    pending: &mut Pending,
+) {
+    while let Some(pos) = queue.pop_front() {
+        let level = chunk.light(channel, pos.x as usize, pos.y as usize, pos.z as usize);
+        for (direction, dir) in DIRECTIONS.iter().enumerate() {
+            let next_level = falloff(channel, direction, level);
+            if next_level == 0 {
+                continue;
+            }
+
+            let next = pos + IVec3::from(*dir);
+            if next.y < 0 || next.y >= CHUNK_SIZE_Y as i32 {
+                continue;
+            }
+            if next.x < 0
+                || next.x >= CHUNK_SIZE_X as i32
+                || next.z < 0
+                || next.z >= CHUNK_SIZE_Z as i32
+            {
+                let (offset, local) = BlockPos(next).split();
+                pending
+                    .entry(chunk_id + offset.0)
+                    .or_insert_with(Vec::new)
+                    .push((local.0, next_level));
+                continue;
+            }
+
+            let (x, y, z) = (next.x as usize, next.y as usize, next.z as usize);
+            if next_level > chunk.light(channel, x, y, z) && !is_opaque(chunk.values[x][y][z]) {
+                chunk.set_light(channel, x, y, z, next_level);
+                queue.push_back(next);
+            }
+        }
+    }
+}
+
+/// Fills every column with full sky light down to its highest opaque block, queueing the cells
+/// that could spread sideways into a neighbouring column
+fn seed_sky(chunk: &mut Chunk, queue: &mut VecDeque<IVec3>) {
+    let height = |chunk: &Chunk, x: i32, z: i32| {
+        if x < 0 || x >= CHUNK_SIZE_X as i32 || z < 0 || z >= CHUNK_SIZE_Z as i32 {
+            // The neighbouring column is in another chunk, assume the worst
+            CHUNK_SIZE_Y
+        } else {
+            chunk
+                .height(Heightmap::Opaque, x as usize, z as usize)
+                .map_or(0, |y| y + 1)
+        }
+    };
+
+    for x in 0..CHUNK_SIZE_X {
+        for z in 0..CHUNK_SIZE_Z {
+            let bottom = height(chunk, x as i32, z as i32);
+            let (xi, zi) = (x as i32, z as i32);
+            let highest_neighbor = height(chunk, xi + 1, zi)
+                .max(height(chunk, xi - 1, zi))
+                .max(height(chunk, xi, zi + 1))
+                .max(height(chunk, xi, zi - 1));
+
+            for y in bottom..CHUNK_SIZE_Y {
+                chunk.set_light(LightChannel::Sky, x, y, z, MAX_LIGHT);
+                if y < highest_neighbor {
+                    queue.push_back(IVec3::new(xi, y as i32, zi));
+                }
+            }
+        }
+    }
+}
+
+/// Gives every emitting block its own level of block light. Opaque emitters hold light too, so
+/// it can spread out of them.
+fn seed_emitters(chunk: &mut Chunk, queue: &mut VecDeque<IVec3>) {
+    for x in 0..CHUNK_SIZE_X {
+        for y in 0..CHUNK_SIZE_Y {
+            for z in 0..CHUNK_SIZE_Z {
+                let level = emission(chunk.values[x][y][z]);
+                if level > chunk.light(LightChannel::Block, x, y, z) {
+                    chunk.set_light(LightChannel::Block, x, y, z, level);
+                    queue.push_back(IVec3::new(x as i32, y as i32, z as i32));
+                }
+            }
+        }
+    }
+}
+
+fn relight_emitter(
+    world: &World,
+    pos: IVec3,
+    refill: &mut Vec<IVec3>,
+    touched: &mut HashSet<IVec2>,
+) {
+    let level = world.get_block(pos).map_or(0, emission);
+    if level > 0 {
+        set_light(world, LightChannel::Block, pos, level, touched);
+        refill.push(pos);
+    }
+}
+
+/// Pairs of cells across the face of a chunk, the first inside it and the second in the
+/// neighbour that `dir` points from
+fn border(dir: IVec3) -> Vec<(IVec3, IVec3)> {
+    let max_x = CHUNK_SIZE_X as i32 - 1;
+    let max_z = CHUNK_SIZE_Z as i32 - 1;
+
+    let mut cells = Vec::new();
+    for y in 0..CHUNK_SIZE_Y as i32 {
+        if dir.x != 0 {
+            // Light travelling +x enters at x = 0 from the neighbour's last column
+            let (inside, outside) = if dir.x > 0 { (0, max_x) } else { (max_x, 0) };
+            for z in 0..CHUNK_SIZE_Z as i32 {
+                cells.push((IVec3::new(inside, y, z), IVec3::new(outside, y, z)));
+            }
+        } else {
+            let (inside, outside) = if dir.z > 0 { (0, max_z) } else { (max_z, 0) };
+            for x in 0..CHUNK_SIZE_X as i32 {
+                cells.push((IVec3::new(x, y, inside), IVec3::new(x, y, outside)));
+            }
+        }
+    }
+    cells
+}
+
+fn get_light(world: &World, channel: LightChannel, pos: IVec3) -> Option<u8> {
+    if pos.y < 0 || pos.y >= CHUNK_SIZE_Y as i32 {
+        return None;
+    }
+
+    let (chunk_id, local) = BlockPos(pos).split();
+    let (x, y, z) = local.index();
+    world
+        .chunks
+        .get(&chunk_id.0)
+        .map(|chunk| chunk.light(channel, x, y, z))
+}
+
+fn set_light(
+    world: &World,
+    channel: LightChannel,
+    pos: IVec3,
+    level: u8,
+    touched: &mut HashSet<IVec2>,
+) {
+    if pos.y < 0 || pos.y >= CHUNK_SIZE_Y as i32 {
+        return;
+    }
+
+    let (chunk_id, local) = BlockPos(pos).split();
+    let (x, y, z) = local.index();
+    if let Some(mut chunk) = world.chunks.get_mut(&chunk_id.0) {
+        chunk.set_light(channel, x, y, z, level);
+        touched.insert(chunk_id.0);
+    }
+}
@@ -0,0 +1,366 @@
+use bevy::prelude::{IVec3, Vec3};
+
+use super::block::{is_fluid, BlockModel, BLOCKS};
+use super::chunk::CHUNK_SIZE_Y;
+use super::coords::BlockPos;
+use super::world::World;
+
+pub const PLAYER_WIDTH: f32 = 0.6;
+pub const PLAYER_HEIGHT: f32 = 1.8;
+/// Height of the camera above the player's feet
+pub const EYE_HEIGHT: f32 = 1.62;
+
+pub const GRAVITY: f32 = 32.0;
+pub const TERMINAL_VELOCITY: f32 = 78.0;
+pub const JUMP_VELOCITY: f32 = 9.0;
+pub const WALK_SPEED: f32 = 4.3;
+
+pub const SWIM_SPEED: f32 = 2.0;
+/// Pull down while in a fluid, much weaker than in the air
+pub const FLUID_GRAVITY: f32 = 6.0;
+/// Push up once the eyes are under, slightly less than `FLUID_GRAVITY` so bodies sink slowly and
+/// bob at the surface
+pub const BUOYANCY: f32 = 5.0;
+/// Acceleration up while holding jump in a fluid
+pub const SWIM_UP_ACCELERATION: f32 = 30.0;
+pub const SWIM_UP_SPEED: f32 = 4.0;
+/// Fraction of vertical speed a body keeps after a second in a fluid
+pub const FLUID_DRAG: f32 = 0.1;
+
+/// Gap kept between a body and the blocks it touches, so rounding never puts it inside them
+const EPSILON: f32 = 1e-4;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Vec3,
+    pub max: Vec3,
+}
+
+impl Aabb {
+    /// Box of the given size standing on `feet`
+    pub fn from_feet(feet: Vec3, width: f32, height: f32) -> Self {
+        let half = Vec3::new(width / 2.0, 0.0, width / 2.0);
+        Aabb {
+            min: feet - half,
+            max: feet + half + Vec3::new(0.0, height, 0.0),
+        }
+    }
+
+    pub fn player(feet: Vec3) -> Self {
+        Aabb::from_feet(feet, PLAYER_WIDTH, PLAYER_HEIGHT)
+    }
+
+    #[inline]
+    pub fn translate(&self, offset: Vec3) -> Self {
+        Aabb {
+            min: self.min + offset,
+            max: self.max + offset,
+        }
+    }
+}
+
+/// Result of moving a body through the world
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Movement {
+    /// How far it actually moved
+    pub moved: Vec3,
+    /// Whether it was stopped along x, y and z
+    pub blocked: [bool; 3],
+    /// Whether it's resting on something solid
+    pub on_ground: bool,
+}
+
+/// Whether a block stops movement. Unloaded chunks and everything below the world are solid, so
+/// nothing falls out of it while chunks load.
+pub fn is_solid(world: &World, pos: IVec3) -> bool {
+    if pos.y < 0 {
+        return true;
+    }
+    if pos.y >= CHUNK_SIZE_Y as i32 {
+        return false;
+    }
+
+    world
+        .get_block(pos)
+        .map_or(true, |block| BLOCKS[block as usize].solid)
+}
+
+/// Whether a point is inside a fluid. Fluids aren't solid, so this is the only way they affect
+/// movement.
+pub fn in_fluid(world: &World, pos: Vec3) -> bool {
+    world
+        .get_block(BlockPos::from_world(pos).0)
+        .map_or(false, is_fluid)
+}
+
+/// How far `aabb` can move along `axis` before it hits a solid block, up to `distance`
+pub fn sweep_axis(world: &World, aabb: &Aabb, axis: usize, distance: f32) -> f32 {
+    if distance == 0.0 {
+        return 0.0;
+    }
+
+    // Blocks the box covers on the other two axes
+    let (a, b) = match axis {
+        0 => (1, 2),
+        1 => (0, 2),
+        _ => (0, 1),
+    };
+    let range = |axis: usize| {
+        (aabb.min[axis] + EPSILON).floor() as i32..(aabb.max[axis] - EPSILON).ceil() as i32
+    };
+    let layer_is_solid = |layer: i32| {
+        range(a).any(|i| {
+            range(b).any(|j| {
+                let mut pos = IVec3::ZERO;
+                pos[axis] = layer;
+                pos[a] = i;
+                pos[b] = j;
+                is_solid(world, pos)
+            })
+        })
+    };
+
+    if distance > 0.0 {
+        let start = (aabb.max[axis] - EPSILON).ceil() as i32;
+        let end = (aabb.max[axis] + distance).ceil() as i32;
+        for layer in start..end {
+            if layer_is_solid(layer) {
+                return (layer as f32 - aabb.max[axis] - EPSILON).clamp(0.0, distance);
+            }
+        }
+    } else {
+        let start = (aabb.min[axis] + EPSILON).floor() as i32 - 1;
+        let end = (aabb.min[axis] + distance).floor() as i32;
+        for layer in (end..=start).rev() {
+            if layer_is_solid(layer) {
+                return (layer as f32 + 1.0 - aabb.min[axis] + EPSILON).clamp(distance, 0.0);
+            }
+        }
+    }
+    distance
+}
+
+/// Moves a box by `motion` one axis at a time, vertical first, stopping at solid blocks
+pub fn sweep(world: &World, aabb: &Aabb, motion: Vec3) -> Movement {
+    let mut aabb = *aabb;
+    let mut moved = Vec3::ZERO;
+    let mut blocked = [false; 3];
+
+    for axis in [1, 0, 2] {
+        let distance = sweep_axis(world, &aabb, axis, motion[axis]);
+        blocked[axis] = distance != motion[axis];
+        moved[axis] = distance;
+        let mut offset = Vec3::ZERO;
+        offset[axis] = distance;
+        aabb = aabb.translate(offset);
+    }
+
+    Movement {
+        moved,
+        blocked,
+        on_ground: touching_ground(world, &aabb),
+    }
+}
+
+/// Whether there's a solid block just below the box
+pub fn touching_ground(world: &World, aabb: &Aabb) -> bool {
+    sweep_axis(world, aabb, 1, -2.0 * EPSILON) > -2.0 * EPSILON
+}
+
+/// A block hit by `raycast`
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RayHit {
+    pub block: IVec3,
+    /// Face of the block the ray came in through, pointing out of it
+    pub normal: IVec3,
+}
+
+/// First block with a model along a ray, skipping fluids. `direction` must be normalised.
+pub fn raycast(world: &World, origin: Vec3, direction: Vec3, max_distance: f32) -> Option<RayHit> {
+    // Steps from one block boundary to the next, always along the axis whose boundary is closest
+    let mut block = BlockPos::from_world(origin).0;
+    let mut normal = IVec3::ZERO;
+    let step = IVec3::new(
+        direction.x.signum() as i32,
+        direction.y.signum() as i32,
+        direction.z.signum() as i32,
+    );
+    let mut next_boundary = Vec3::ZERO;
+    let mut boundary_step = Vec3::ZERO;
+    for axis in 0..3 {
+        if direction[axis] == 0.0 {
+            next_boundary[axis] = f32::INFINITY;
+            boundary_step[axis] = f32::INFINITY;
+        } else {
+            let boundary = if direction[axis] > 0.0 {
+                block[axis] as f32 + 1.0
+            } else {
+                block[axis] as f32
+            };
+            next_boundary[axis] = (boundary - origin[axis]) / direction[axis];
+            boundary_step[axis] = 1.0 / direction[axis].abs();
+        }
+    }
+
+    let mut distance = 0.0;
+    while distance <= max_distance {
+        if let Some(id) = world.get_block(block) {
+            let target = &BLOCKS[id as usize];
+            if target.model != BlockModel::None && !target.fluid {
+                return Some(RayHit { block, normal });
+            }
+        }
+
+        let axis = if next_boundary.x < next_boundary.y && next_boundary.x < next_boundary.z {
+            0
+        } else if next_boundary.y < next_boundary.z {
+            1
+        } else {
+            2
+        };
+        distance = next_boundary[axis];
+        next_boundary[axis] += boundary_step[axis];
+        block[axis] += step[axis];
+        normal = IVec3::ZERO;
+        normal[axis] = -step[axis];
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::prelude::IVec2;
+
+    use super::*;
+    use crate::block::STONE;
+    use crate::chunk::Chunk;
+    use crate::preset::WorldPreset;
+
+    /// Empty chunks on both sides of the origin, so nothing is solid until it's placed
+    fn empty_world() -> World {
+        let world = World::new(WorldPreset::Void, 0, false);
+        for x in -2..2 {
+            for z in -2..2 {
+                let chunk_id = IVec2::new(x, z);
+                world.chunks.insert(chunk_id, Chunk::new(chunk_id));
+            }
+        }
+        world
+    }
+
+    /// Fills every block from `min` to `max`, inclusive
+    fn fill(world: &World, min: IVec3, max: IVec3) {
+        for x in min.x..=max.x {
+            for y in min.y..=max.y {
+                for z in min.z..=max.z {
+                    let (chunk_id, local) = BlockPos(IVec3::new(x, y, z)).split();
+                    let (x, y, z) = local.index();
+                    world.chunks.get_mut(&chunk_id.0).unwrap().values[x][y][z] = STONE;
+                }
+            }
+        }
+    }
+
+    /// Ground with its top at y = 11
+    fn floor(world: &World) {
+        fill(world, IVec3::new(-48, 10, -48), IVec3::new(47, 10, 47));
+    }
+
+    fn close(a: f32, b: f32) -> bool {
+        (a - b).abs() < 0.01
+    }
+
+    #[test]
+    fn lands_on_ground() {
+        let world = empty_world();
+        floor(&world);
+
+        let movement = sweep(
+            &world,
+            &Aabb::player(Vec3::new(0.5, 12.0, 0.5)),
+            Vec3::new(0.0, -5.0, 0.0),
+        );
+        assert!(close(movement.moved.y, -1.0), "{:?}", movement);
+        assert!(movement.blocked[1]);
+        assert!(movement.on_ground);
+    }
+
+    #[test]
+    fn falls_when_nothing_is_below() {
+        let world = empty_world();
+        floor(&world);
+
+        let aabb = Aabb::player(Vec3::new(0.5, 15.0, 0.5));
+        assert!(!touching_ground(&world, &aabb));
+        let movement = sweep(&world, &aabb, Vec3::new(0.0, -1.0, 0.0));
+        assert!(close(movement.moved.y, -1.0));
+        assert!(!movement.blocked[1]);
+        assert!(!movement.on_ground);
+    }
+
+    #[test]
+    fn blocked_by_wall() {
+        let world = empty_world();
+        floor(&world);
+        fill(&world, IVec3::new(2, 11, -4), IVec3::new(2, 13, 4));
+
+        let movement = sweep(
+            &world,
+            &Aabb::player(Vec3::new(0.5, 11.0, 0.5)),
+            Vec3::new(3.0, 0.0, 0.0),
+        );
+        // Stops with its side against the wall, 0.3 from its centre
+        assert!(close(movement.moved.x, 1.2), "{:?}", movement);
+        assert!(movement.blocked[0]);
+        assert!(!movement.blocked[2]);
+        assert!(movement.on_ground);
+    }
+
+    #[test]
+    fn ledge_needs_a_jump() {
+        let world = empty_world();
+        floor(&world);
+        fill(&world, IVec3::new(2, 11, -4), IVec3::new(4, 11, 4));
+
+        let movement = sweep(
+            &world,
+            &Aabb::player(Vec3::new(0.5, 11.0, 0.5)),
+            Vec3::new(2.0, 0.0, 0.0),
+        );
+        assert!(close(movement.moved.x, 1.2), "{:?}", movement);
+        assert_eq!(movement.moved.y, 0.0);
+        assert!(movement.blocked[0]);
+    }
+
+    #[test]
+    fn lands_across_negative_chunk_border() {
+        let world = empty_world();
+        // Only under the half of the body in chunk (-2, -2), the other half is over chunk (-1, -1)
+        fill(&world, IVec3::new(-33, 10, -33), IVec3::new(-33, 10, -33));
+
+        let movement = sweep(
+            &world,
+            &Aabb::player(Vec3::new(-32.0, 12.0, -32.0)),
+            Vec3::new(0.0, -5.0, 0.0),
+        );
+        assert!(close(movement.moved.y, -1.0), "{:?}", movement);
+        assert!(movement.on_ground);
+    }
+
+    #[test]
+    fn blocked_across_negative_chunk_border() {
+        let world = empty_world();
+        floor(&world);
+        fill(&world, IVec3::new(-34, 11, -34), IVec3::new(-34, 12, -20));
+
+        let movement = sweep(
+            &world,
+            &Aabb::player(Vec3::new(-28.5, 11.0, -28.5)),
+            Vec3::new(-10.0, 0.0, 0.0),
+        );
+        // Walks out of chunk (-1, -1) into chunk (-2, -1) and stops against the wall in it
+        assert!(close(movement.moved.x, -4.2), "{:?}", movement);
+        assert!(movement.blocked[0]);
+        assert!(movement.on_ground);
+    }
+}
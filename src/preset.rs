@@ -0,0 +1,140 @@
+use bevy::prelude::IVec3;
+
+use super::block::{self, BLOCKS, STONE};
+use super::chunk::*;
+
+const AMPLIFIED_AMPLITUDE: f32 = 3.0;
+const VOID_PLATFORM_Y: usize = 48;
+const VOID_PLATFORM_RADIUS: i32 = 2;
+const DEBUG_GRID_Y: usize = 40;
+
+/// How terrain is generated, picked once at startup
+pub enum WorldPreset {
+    Default,
+    /// The default noise terrain stretched vertically
+    Amplified,
+    /// One block per layer, listed from the bottom up
+    Superflat(Vec<u16>),
+    /// Nothing but a small platform at the origin to spawn on
+    Void,
+    /// Every registered block laid out on a grid
+    DebugGrid,
+}
+
+impl WorldPreset {
+    /// `layers` is only used by superflat, e.g. `stone,3*dirt,grass`
+    pub fn parse(name: &str, layers: Option<&str>) -> Result<Self, String> {
+        match name {
+            "default" => Ok(WorldPreset::Default),
+            "amplified" => Ok(WorldPreset::Amplified),
+            "superflat" => Ok(WorldPreset::Superflat(parse_layers(
+                layers.unwrap_or("8*stone,3*dirt,grass"),
+            )?)),
+            "void" => Ok(WorldPreset::Void),
+            "debug" => Ok(WorldPreset::DebugGrid),
+            _ => Err(format!(
+                "Unknown preset {:?}, expected one of default, amplified, superflat, void or debug",
+                name
+            )),
+        }
+    }
+
+    /// Whether trees and other features are placed on top of the terrain
+    pub fn decorates(&self) -> bool {
+        matches!(self, WorldPreset::Default | WorldPreset::Amplified)
+    }
+
+    /// How much the noise terrain is stretched vertically, None for presets without noise terrain
+    pub fn amplitude(&self) -> Option<f32> {
+        match self {
+            WorldPreset::Default => Some(1.0),
+            WorldPreset::Amplified => Some(AMPLIFIED_AMPLITUDE),
+            _ => None,
+        }
+    }
+
+    pub fn generate(&self, chunk: &mut Chunk, pos: IVec3, seed: i32) {
+        match self {
+            WorldPreset::Default | WorldPreset::Amplified => {
+                // Computes its own heightmaps
+                chunk.generate_scaled(pos, self.amplitude().unwrap(), seed);
+                return;
+            }
+            WorldPreset::Superflat(layers) => {
+                for x in 0..CHUNK_SIZE_X {
+                    for z in 0..CHUNK_SIZE_Z {
+                        for (y, block) in layers.iter().take(CHUNK_SIZE_Y).enumerate() {
+                            chunk.values[x][y][z] = *block;
+                        }
+                    }
+                }
+            }
+            WorldPreset::Void => {
+                for x in 0..CHUNK_SIZE_X {
+                    for z in 0..CHUNK_SIZE_Z {
+                        let world_x = pos.x + x as i32;
+                        let world_z = pos.z + z as i32;
+                        if world_x.abs() <= VOID_PLATFORM_RADIUS
+                            && world_z.abs() <= VOID_PLATFORM_RADIUS
+                        {
+                            chunk.values[x][VOID_PLATFORM_Y][z] = STONE;
+                        }
+                    }
+                }
+            }
+            WorldPreset::DebugGrid => {
+                // Blocks sit on every other column starting at the origin so none of them touch
+                let count = BLOCKS.len() - 1;
+                let width = (count as f32).sqrt().ceil() as i32;
+                for x in 0..CHUNK_SIZE_X {
+                    for z in 0..CHUNK_SIZE_Z {
+                        let world_x = pos.x + x as i32;
+                        let world_z = pos.z + z as i32;
+                        if world_x < 0 || world_z < 0 || world_x % 2 != 0 || world_z % 2 != 0 {
+                            continue;
+                        }
+
+                        let (grid_x, grid_z) = (world_x / 2, world_z / 2);
+                        if grid_x >= width {
+                            continue;
+                        }
+                        let index = (grid_z * width + grid_x) as usize;
+                        if index < count {
+                            chunk.values[x][DEBUG_GRID_Y][z] = index as u16 + 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        chunk.compute_heightmaps();
+    }
+}
+
+fn parse_layers(layers: &str) -> Result<Vec<u16>, String> {
+    let mut blocks = Vec::new();
+    for layer in layers.split(',').map(str::trim) {
+        let (count, name) = match layer.split_once('*') {
+            Some((count, name)) => (
+                count
+                    .trim()
+                    .parse::<usize>()
+                    .map_err(|_| format!("Invalid layer count in {:?}", layer))?,
+                name.trim(),
+            ),
+            None => (1, layer),
+        };
+        let block = block::from_name(name).ok_or_else(|| format!("Unknown block {:?}", name))?;
+        // Checked before extending so a huge count is rejected rather than allocated
+        let total = blocks.len().saturating_add(count);
+        if total > CHUNK_SIZE_Y {
+            return Err(format!(
+                "Superflat has {} layers but chunks are only {} high",
+                total, CHUNK_SIZE_Y
+            ));
+        }
+        blocks.extend(std::iter::repeat(block).take(count));
+    }
+
+    Ok(blocks)
+}
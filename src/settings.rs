@@ -0,0 +1,318 @@
+use bevy::{prelude::*, render::camera::PerspectiveProjection};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, ops::RangeInclusive, path::PathBuf};
+
+use super::lifecycle::NEIGHBOR_STAGES;
+
+/// Something the player can do, bound to one or more inputs
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Forward,
+    Back,
+    Left,
+    Right,
+    /// Jump, swim or fly up
+    Jump,
+    /// Fly down
+    Descend,
+    ToggleFly,
+    ReleaseCursor,
+    Break,
+    Place,
+    HotbarNext,
+    HotbarPrevious,
+}
+
+const ACTIONS: [(&str, Action); 12] = [
+    ("forward", Action::Forward),
+    ("back", Action::Back),
+    ("left", Action::Left),
+    ("right", Action::Right),
+    ("jump", Action::Jump),
+    ("descend", Action::Descend),
+    ("toggle_fly", Action::ToggleFly),
+    ("release_cursor", Action::ReleaseCursor),
+    ("break", Action::Break),
+    ("place", Action::Place),
+    ("hotbar_next", Action::HotbarNext),
+    ("hotbar_previous", Action::HotbarPrevious),
+];
+
+/// Loaded chunks form a circle, and even the centre chunk only meshes once the corners of the
+/// square `NEIGHBOR_STAGES` chunks around it are inside, so anything less shows nothing
+pub const MIN_VIEW_DISTANCE: usize = min_view_distance();
+/// Each step up loads a whole ring of chunks more, this many is already thousands of them
+pub const MAX_VIEW_DISTANCE: usize = 32;
+/// Vertical field of view in degrees. Much wider and the half-fov maths used to prioritise chunks
+/// in view breaks down, much narrower and the projection is degenerate.
+const FOV_RANGE: RangeInclusive<f32> = 30.0..=120.0;
+const SENSITIVITY_RANGE: RangeInclusive<f32> = 0.0001..=0.02;
+const GAMEPAD_SENSITIVITY_RANGE: RangeInclusive<f32> = 0.1..=20.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Binding {
+    Key(KeyCode),
+    Mouse(MouseButton),
+    /// A button on whichever gamepad was connected last
+    Gamepad(GamepadButtonType),
+}
+
+/// Loaded from `settings.ron` in the user's config directory, and written back whenever it changes
+#[derive(Serialize, Deserialize)]
+#[serde(default)]
+pub struct Settings {
+    pub bindings: HashMap<Action, Vec<Binding>>,
+    /// Radians turned per pixel of mouse movement
+    pub sensitivity: f32,
+    /// Radians turned per second with the look stick all the way over
+    pub gamepad_sensitivity: f32,
+    /// How far sticks have to move before they do anything, from 0 to 1
+    pub dead_zone: f32,
+    /// Vertical field of view in degrees
+    pub fov: f32,
+    /// Radius in chunks, can be changed while playing
+    pub view_distance: usize,
+    pub fly_speed: f32,
+    /// Megabytes of unloaded chunks kept around in case the character comes back
+    pub chunk_cache_mb: usize,
+    /// Finished chunk meshes handed to the GPU each frame, the rest wait for the next one
+    pub mesh_uploads_per_frame: usize,
+}
+
+impl Default for Settings {
+    fn default() -> Self {
+        use Binding::{Gamepad, Key, Mouse};
+        use GamepadButtonType::*;
+
+        let bindings = vec![
+            (Action::Forward, vec![Key(KeyCode::W)]),
+            (Action::Back, vec![Key(KeyCode::S)]),
+            (Action::Left, vec![Key(KeyCode::A)]),
+            (Action::Right, vec![Key(KeyCode::D)]),
+            (Action::Jump, vec![Key(KeyCode::Space), Gamepad(South)]),
+            (Action::Descend, vec![Key(KeyCode::LShift), Gamepad(East)]),
+            (Action::ToggleFly, vec![Key(KeyCode::F), Gamepad(North)]),
+            (
+                Action::ReleaseCursor,
+                vec![Key(KeyCode::Escape), Gamepad(Start)],
+            ),
+            (
+                Action::Break,
+                vec![Mouse(MouseButton::Left), Gamepad(RightTrigger2)],
+            ),
+            (
+                Action::Place,
+                vec![Mouse(MouseButton::Right), Gamepad(LeftTrigger2)],
+            ),
+            (
+                Action::HotbarNext,
+                vec![Key(KeyCode::E), Gamepad(RightTrigger)],
+            ),
+            (
+                Action::HotbarPrevious,
+                vec![Key(KeyCode::Q), Gamepad(LeftTrigger)],
+            ),
+        ];
+
+        Settings {
+            bindings: bindings.into_iter().collect(),
+            sensitivity: 0.002,
+            gamepad_sensitivity: 3.0,
+            dead_zone: 0.15,
+            fov: 85.0,
+            view_distance: 16,
+            fly_speed: 500.0,
+            chunk_cache_mb: 256,
+            mesh_uploads_per_frame: 4,
+        }
+    }
+}
+
+impl Settings {
+    pub fn path() -> Option<PathBuf> {
+        dirs::config_dir().map(|dir| dir.join("steve").join("settings.ron"))
+    }
+
+    /// Falls back to the defaults if there's no settings file yet or it can't be read
+    pub fn load() -> Self {
+        let path = match Settings::path() {
+            Some(path) => path,
+            None => return Settings::default(),
+        };
+
+        match std::fs::read_to_string(&path) {
+            Ok(contents) => {
+                let mut settings: Settings = ron::from_str(&contents).unwrap_or_else(|err| {
+                    eprintln!("Ignoring invalid settings in {:?}: {}", path, err);
+                    Settings::default()
+                });
+                // Actions added since the file was written keep their default bindings
+                for (action, bindings) in Settings::default().bindings {
+                    settings.bindings.entry(action).or_insert(bindings);
+                }
+                settings.view_distance = settings
+                    .view_distance
+                    .clamp(MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE);
+                settings.mesh_uploads_per_frame = settings.mesh_uploads_per_frame.max(1);
+                let defaults = Settings::default();
+                if !(0.0..1.0).contains(&settings.dead_zone) {
+                    settings.dead_zone = defaults.dead_zone;
+                }
+                if !FOV_RANGE.contains(&settings.fov) {
+                    settings.fov = defaults.fov;
+                }
+                if !SENSITIVITY_RANGE.contains(&settings.sensitivity) {
+                    settings.sensitivity = defaults.sensitivity;
+                }
+                if !GAMEPAD_SENSITIVITY_RANGE.contains(&settings.gamepad_sensitivity) {
+                    settings.gamepad_sensitivity = defaults.gamepad_sensitivity;
+                }
+                settings
+            }
+            Err(_) => {
+                // Written out on the first run so there's a file to edit
+                let settings = Settings::default();
+                if let Err(err) = settings.save() {
+                    eprintln!("Couldn't save settings: {}", err);
+                }
+                settings
+            }
+        }
+    }
+
+    pub fn save(&self) -> Result<(), String> {
+        let path = Settings::path().ok_or("No config directory to save settings in")?;
+        let contents = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())
+            .map_err(|err| err.to_string())?;
+
+        if let Some(dir) = path.parent() {
+            std::fs::create_dir_all(dir).map_err(|err| err.to_string())?;
+        }
+        std::fs::write(&path, contents).map_err(|err| err.to_string())
+    }
+
+    pub fn bindings(&self, action: Action) -> &[Binding] {
+        self.bindings.get(&action).map_or(&[], Vec::as_slice)
+    }
+
+    /// Handles `set <setting> <value>` and
+    /// `bind <action> <key or mouse button>...`. Returns what happened.
+    pub fn command(&mut self, command: &str, args: &[&str]) -> Result<String, String> {
+        match (command, args) {
+            ("set", [name, value]) => {
+                let invalid = || format!("Invalid value {:?} for {}", value, name);
+                match *name {
+                    "sensitivity" => {
+                        self.sensitivity = parse_in_range(name, value, SENSITIVITY_RANGE)?
+                    }
+                    "gamepad_sensitivity" => {
+                        self.gamepad_sensitivity =
+                            parse_in_range(name, value, GAMEPAD_SENSITIVITY_RANGE)?
+                    }
+                    "dead_zone" => {
+                        let dead_zone: f32 = value.parse().map_err(|_| invalid())?;
+                        if !(0.0..1.0).contains(&dead_zone) {
+                            return Err("dead_zone has to be at least 0 and below 1".to_string());
+                        }
+                        self.dead_zone = dead_zone;
+                    }
+                    "fov" => self.fov = parse_in_range(name, value, FOV_RANGE)?,
+                    "view_distance" => {
+                        let view_distance = value.parse().map_err(|_| invalid())?;
+                        if !(MIN_VIEW_DISTANCE..=MAX_VIEW_DISTANCE).contains(&view_distance) {
+                            return Err(format!(
+                                "view_distance has to be from {} to {}",
+                                MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE
+                            ));
+                        }
+                        self.view_distance = view_distance;
+                    }
+                    "fly_speed" => self.fly_speed = value.parse().map_err(|_| invalid())?,
+                    "chunk_cache_mb" => {
+                        self.chunk_cache_mb = value.parse().map_err(|_| invalid())?
+                    }
+                    "mesh_uploads_per_frame" => {
+                        let uploads = value.parse().map_err(|_| invalid())?;
+                        if uploads == 0 {
+                            return Err("mesh_uploads_per_frame has to be at least 1".to_string());
+                        }
+                        self.mesh_uploads_per_frame = uploads;
+                    }
+                    _ => return Err(format!("Unknown setting {:?}", name)),
+                }
+                Ok(format!("Set {} to {}", name, value))
+            }
+            ("bind", [action, inputs @ ..]) if !inputs.is_empty() => {
+                let action = ACTIONS
+                    .iter()
+                    .find(|(name, _)| name == action)
+                    .map(|(_, action)| *action)
+                    .ok_or_else(|| format!("Unknown action {:?}", action))?;
+                let bindings = inputs
+                    .iter()
+                    .map(|input| parse_binding(input))
+                    .collect::<Result<Vec<_>, _>>()?;
+                self.bindings.insert(action, bindings);
+                Ok(format!("Bound {:?} to {}", action, inputs.join(", ")))
+            }
+            ("set", _) => {
+                Err("Usage: set <sensitivity|gamepad_sensitivity|dead_zone|fov|view_distance|fly_speed|chunk_cache_mb|mesh_uploads_per_frame> <value>".to_string())
+            }
+            _ => Err("Usage: bind <action> <key or mouse button>...".to_string()),
+        }
+    }
+}
+
+const fn min_view_distance() -> usize {
+    let corner = 2 * NEIGHBOR_STAGES * NEIGHBOR_STAGES;
+    let mut radius = NEIGHBOR_STAGES + 1;
+    while radius * radius <= corner {
+        radius += 1;
+    }
+    radius
+}
+
+/// Parses the value of a setting that has to stay inside `range`
+fn parse_in_range(name: &str, value: &str, range: RangeInclusive<f32>) -> Result<f32, String> {
+    let parsed: f32 = value
+        .parse()
+        .map_err(|_| format!("Invalid value {:?} for {}", value, name))?;
+    if !range.contains(&parsed) {
+        return Err(format!(
+            "{} has to be from {} to {}",
+            name,
+            range.start(),
+            range.end()
+        ));
+    }
+    Ok(parsed)
+}
+
+/// Keys as Bevy spells them, like `Space` or `LShift`, or bindings as they're written in the
+/// settings file, like `Mouse(Left)` or `Gamepad(South)`
+fn parse_binding(input: &str) -> Result<Binding, String> {
+    ron::from_str::<Binding>(input)
+        .or_else(|_| ron::from_str::<KeyCode>(input).map(Binding::Key))
+        .map_err(|_| format!("Unknown key or mouse button {:?}", input))
+}
+
+/// Writes the settings back whenever something changes them
+pub fn save_settings(settings: Res<Settings>) {
+    if settings.is_changed() && !settings.is_added() {
+        if let Err(err) = settings.save() {
+            eprintln!("Couldn't save settings: {}", err);
+        }
+    }
+}
+
+/// Keeps the camera's field of view in line with the settings
+pub fn apply_fov(
+    settings: Res<Settings>,
+    mut projection: Query<&mut PerspectiveProjection, With<super::Character>>,
+) {
+    if settings.is_changed() {
+        if let Ok(mut projection) = projection.single_mut() {
+            projection.fov = settings.fov.to_radians();
+        }
+    }
+}
@@ -0,0 +1,17 @@
+#version 450
+
+layout(location = 0) out vec4 o_Target;
+layout(location = 0) in vec3 v_direction;
+
+layout(set = 2, binding = 0) uniform SkyMaterial_horizon {
+    vec4 horizon;
+};
+layout(set = 2, binding = 1) uniform SkyMaterial_zenith {
+    vec4 zenith;
+};
+
+void main() {
+    float height = clamp(normalize(v_direction).y, 0.0, 1.0);
+    // Most of the change happens just above the horizon
+    o_Target = vec4(mix(horizon.xyz, zenith.xyz, sqrt(height)), 1.0);
+}
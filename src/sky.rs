@@ -0,0 +1,124 @@
+use bevy::{
+    prelude::*,
+    reflect::TypeUuid,
+    render::{
+        pipeline::{CullMode, PipelineDescriptor, RenderPipeline},
+        render_graph::{base, AssetRenderResourcesNode, RenderGraph},
+        renderer::RenderResources,
+        shader::{ShaderStage, ShaderStages},
+    },
+};
+use std::{f32::consts::SQRT_2, sync::Arc};
+
+use super::chunk::CHUNK_SIZE_X;
+use super::lifecycle::NEIGHBOR_STAGES;
+use super::physics;
+use super::world::World;
+use super::Character;
+
+/// Far enough out to be behind all loaded terrain, close enough to stay inside the far plane
+const SKY_RADIUS: f32 = 5000.0;
+/// Fraction of the fog's range over which it fades in
+const FOG_FADE: f32 = 0.4;
+
+pub const UNDERWATER_FOG: [f32; 3] = [0.05, 0.2, 0.45];
+/// Fog starts right at the camera under water, which also tints everything blue
+pub const UNDERWATER_FOG_RANGE: (f32, f32) = (0.0, 24.0);
+
+#[derive(RenderResources, Default, TypeUuid)]
+#[uuid = "6a3c1f3e-5d0b-4bde-9a47-2e0c8f9b7d21"]
+pub struct SkyMaterial {
+    pub horizon: Color,
+    pub zenith: Color,
+}
+
+pub struct SkyMaterialHandle(pub Handle<SkyMaterial>);
+
+/// Dome drawn around the camera
+pub struct Sky;
+
+/// Whether the camera is inside a fluid
+#[derive(Default)]
+pub struct Underwater(pub bool);
+
+/// Where fog starts and where it hides everything, so the edge of the meshed chunks can't be seen.
+/// Loaded chunks form a circle, so meshing falls furthest behind along the diagonals, by
+/// `NEIGHBOR_STAGES` chunks on each axis. Another chunk is allowed for the camera being off the
+/// centre of its chunk and for the near side of the first chunk that isn't meshed.
+pub fn fog_range(view_distance: usize) -> (f32, f32) {
+    let meshed = view_distance as f32 - NEIGHBOR_STAGES as f32 * SQRT_2 - 1.0;
+    let end = meshed.max(0.5) * CHUNK_SIZE_X as f32;
+    (end * (1.0 - FOG_FADE), end)
+}
+
+pub fn setup_sky(
+    mut commands: Commands,
+    mut meshes: ResMut<Assets<Mesh>>,
+    mut sky_materials: ResMut<Assets<SkyMaterial>>,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut shaders: ResMut<Assets<Shader>>,
+    mut render_graph: ResMut<RenderGraph>,
+) {
+    let mut pipeline = PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(
+            ShaderStage::Vertex,
+            include_str!("sky.vert"),
+        )),
+        fragment: Some(shaders.add(Shader::from_glsl(
+            ShaderStage::Fragment,
+            include_str!("sky.frag"),
+        ))),
+    });
+    // Seen from the inside
+    pipeline.primitive.cull_mode = CullMode::None;
+    let pipeline_handle = pipelines.add(pipeline);
+
+    render_graph.add_system_node(
+        "sky_material",
+        AssetRenderResourcesNode::<SkyMaterial>::new(true),
+    );
+    render_graph
+        .add_node_edge("sky_material", base::node::MAIN_PASS)
+        .unwrap();
+
+    let sky_material_handle = sky_materials.add(SkyMaterial::default());
+    commands
+        .spawn_bundle(MeshBundle {
+            mesh: meshes.add(Mesh::from(shape::Icosphere {
+                radius: 1.0,
+                subdivisions: 3,
+            })),
+            render_pipelines: RenderPipelines::from_pipelines(vec![RenderPipeline::new(
+                pipeline_handle,
+            )]),
+            transform: Transform::from_scale(Vec3::splat(SKY_RADIUS)),
+            ..Default::default()
+        })
+        .insert(sky_material_handle.clone())
+        .insert(Sky);
+    commands.insert_resource(SkyMaterialHandle(sky_material_handle));
+}
+
+/// Keeps the sky centred on the camera so it never gets any closer
+pub fn follow_camera(
+    character: Query<&Transform, (With<Character>, Without<Sky>)>,
+    mut sky: Query<&mut Transform, With<Sky>>,
+) {
+    if let (Ok(character), Ok(mut sky)) = (character.single(), sky.single_mut()) {
+        sky.translation = character.translation;
+    }
+}
+
+pub fn detect_underwater(
+    character: Query<&Character>,
+    world: Res<Arc<World>>,
+    mut underwater: ResMut<Underwater>,
+) {
+    if let Ok(character) = character.single() {
+        let in_fluid = physics::in_fluid(&world, character.position);
+        // Only written on change, so the materials aren't rebuilt every frame
+        if underwater.0 != in_fluid {
+            underwater.0 = in_fluid;
+        }
+    }
+}
@@ -0,0 +1,109 @@
+use bevy::prelude::*;
+use std::sync::Arc;
+
+use super::block::{is_fluid, AIR, LEAVES};
+use super::chunk::{Heightmap, CHUNK_SIZE_Y};
+use super::coords::BlockPos;
+use super::lifecycle::{ChunkStates, ChunkStatus};
+use super::physics;
+use super::world::World;
+use super::Character;
+
+/// How far from the origin to look for somewhere to stand, in blocks. Less than a chunk, so only
+/// the origin's chunk and its neighbours are searched. The waiting character keeps them in view,
+/// and even at `MIN_VIEW_DISTANCE` everything around them gets decorated, so the search finishes.
+const SEARCH_RADIUS: i32 = 24;
+
+/// The character is held in place until its spawn chunk has been generated and decorated
+pub struct AwaitingSpawn;
+
+/// Where a character waiting to spawn is kept, high enough that the chunks around the origin load
+pub fn holding_position() -> Vec3 {
+    Vec3::new(0.5, CHUNK_SIZE_Y as f32, 0.5)
+}
+
+/// Moves the character to the world's spawn point once there is one, then lets physics run
+pub fn place_at_spawn(
+    mut commands: Commands,
+    mut character: Query<(Entity, &mut Transform, &mut Character), With<AwaitingSpawn>>,
+    mut columns: Local<Vec<IVec2>>,
+    world: Res<Arc<World>>,
+    states: Res<ChunkStates>,
+) {
+    let (entity, mut transform, mut character) = match character.single_mut() {
+        Ok(character) => character,
+        Err(_) => return,
+    };
+
+    let feet = match world.spawn() {
+        Some(spawn) => spawn,
+        None => match find_spawn(&mut columns, &world, &states) {
+            Some(spawn) => {
+                world.set_spawn(spawn);
+                spawn
+            }
+            None => return,
+        },
+    };
+
+    let eyes = feet + Vec3::new(0.0, physics::EYE_HEIGHT, 0.0);
+    character.position = eyes;
+    character.previous_position = eyes;
+    character.velocity = Vec3::ZERO;
+    transform.translation = eyes;
+    commands.entity(entity).remove::<AwaitingSpawn>();
+}
+
+/// The closest column to the origin with solid ground that isn't a tree and two blocks of air
+/// above it. None while the chunks that decide it are still being generated, which includes the
+/// neighbours of its chunk as their trees can reach over it.
+fn find_spawn(columns: &mut Vec<IVec2>, world: &World, states: &ChunkStates) -> Option<Vec3> {
+    // Closest first, worked out once as this runs every frame until the chunks are ready
+    if columns.is_empty() {
+        for x in -SEARCH_RADIUS..=SEARCH_RADIUS {
+            for z in -SEARCH_RADIUS..=SEARCH_RADIUS {
+                if x * x + z * z <= SEARCH_RADIUS * SEARCH_RADIUS {
+                    columns.push(IVec2::new(x, z));
+                }
+            }
+        }
+        columns.sort_by_key(|column| {
+            (
+                column.x * column.x + column.y * column.y,
+                column.x,
+                column.y,
+            )
+        });
+    }
+
+    for &column in columns.iter() {
+        // Columns are checked in order, so a closer one is never skipped for being late
+        let (chunk_id, local) = BlockPos(IVec3::new(column.x, 0, column.y)).split();
+        if !states.neighbors_reached(chunk_id.0, ChunkStatus::Decorated) {
+            return None;
+        }
+
+        let chunk = world.chunks.get(&chunk_id.0)?;
+        let (x, _, z) = local.index();
+        let ground = match chunk.height(Heightmap::MotionBlocking, x, z) {
+            Some(ground) if ground + 2 < CHUNK_SIZE_Y => ground,
+            _ => continue,
+        };
+        let clear = (1..=2).all(|above| !is_fluid(chunk.values[x][ground + above][z]));
+        if chunk.values[x][ground][z] != LEAVES && clear {
+            return Some(Vec3::new(
+                column.x as f32 + 0.5,
+                ground as f32 + 1.0,
+                column.y as f32 + 0.5,
+            ));
+        }
+    }
+
+    // Nowhere dry nearby, so start on top of whatever is at the origin
+    let chunk = world.chunks.get(&IVec2::ZERO)?;
+    let top = (0..CHUNK_SIZE_Y)
+        .rev()
+        .find(|&y| chunk.values[0][y][0] != AIR)
+        .map_or(0, |y| y + 1);
+    Some(Vec3::new(0.5, top as f32, 0.5))
+}
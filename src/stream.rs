@@ -0,0 +1,779 @@
+use bevy::{prelude::*, render::pipeline::RenderPipeline, tasks::AsyncComputeTaskPool};
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, Sender},
+        Arc, Mutex,
+    },
+};
+
+use super::cache::{CachedChunk, ChunkCache};
+use super::chunk::*;
+use super::coords::ChunkPos;
+use super::decoration;
+use super::lifecycle::{CancelToken, ChunkStates, ChunkStatus};
+use super::light;
+use super::settings::{Settings, MAX_VIEW_DISTANCE, MIN_VIEW_DISTANCE};
+use super::world::World;
+use super::{neighbors, Character, ChunkMaterialHandle, ChunkPipelineHandle};
+
+static COUNTER: AtomicUsize = AtomicUsize::new(0);
+static COUNTER2: AtomicUsize = AtomicUsize::new(0);
+static COUNTER3: AtomicUsize = AtomicUsize::new(0);
+/// Tasks that stopped early because their chunk left range
+static CANCELLED_TASKS: AtomicUsize = AtomicUsize::new(0);
+/// Tasks that ran to the end only for their result to be thrown away
+static WASTED_TASKS: AtomicUsize = AtomicUsize::new(0);
+
+/// Jobs allowed to run at once for each thread in the pool, the rest wait where they can still be
+/// reordered
+const JOBS_PER_THREAD: usize = 2;
+/// How much further than the view distance chunks go before they're unloaded, so going back and
+/// forth over a chunk border doesn't unload and load the same chunks over and over
+const UNLOAD_MARGIN: i32 = 2;
+/// Chunks this close are loaded first whichever way the camera faces
+const NEAR_RADIUS: f32 = 2.0;
+/// How much closer chunks in view count as
+const VIEW_WEIGHT: f32 = 0.5;
+/// How much closer chunks straight ahead of the character's movement count as
+const TRAVEL_WEIGHT: f32 = 0.3;
+/// Added to either side of the view, so chunks at the edge of the screen aren't late
+const VIEW_MARGIN: f32 = 0.2;
+/// How far the camera turns before the queue is sorted again
+const REPRIORITISE_ANGLE: f32 = 0.1;
+
+/// Adds chunk loading, generation and meshing around the character. Meshes are only drawn if
+/// there's a renderer.
+pub struct ChunkStreamPlugin;
+
+impl Plugin for ChunkStreamPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(ChunkStates::default())
+            .insert_resource(ChunkCache::default())
+            .insert_resource(ChunkJobs::default())
+            .insert_resource(ChunkPriorityMap::default())
+            .add_system(update_chunk_state.system().label("update_chunk_state"))
+            .add_system(prioritise_chunks.system().after("update_chunk_state"))
+            .add_system(
+                handle_chunk_tasks
+                    .system()
+                    .label("handle_chunk_tasks")
+                    .after("update_chunk_state"),
+            )
+            .add_system(upload_chunk_meshes.system().after("handle_chunk_tasks"));
+    }
+}
+
+/// Jobs finished since startup
+pub struct TaskCounts {
+    pub generated: usize,
+    pub decorated: usize,
+    pub meshed: usize,
+    pub cancelled: usize,
+    pub wasted: usize,
+}
+
+pub fn task_counts() -> TaskCounts {
+    TaskCounts {
+        generated: COUNTER.load(Ordering::Relaxed),
+        decorated: COUNTER3.load(Ordering::Relaxed),
+        meshed: COUNTER2.load(Ordering::Relaxed),
+        cancelled: CANCELLED_TASKS.load(Ordering::Relaxed),
+        wasted: WASTED_TASKS.load(Ordering::Relaxed),
+    }
+}
+
+pub struct ChunkComponent {
+    pub chunk_id: IVec2,
+}
+
+/// Later stages go first, as they're closer to being drawn
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum JobKind {
+    Generate,
+    Decorate,
+    Light,
+    Mesh,
+}
+
+struct Job {
+    chunk_id: IVec2,
+    kind: JobKind,
+    /// Squared distance in chunks from the closest observer when it was submitted
+    distance: i32,
+    /// Breaks ties so jobs submitted first start first
+    order: u64,
+}
+
+impl Job {
+    fn key(&self) -> (JobKind, Reverse<i32>, Reverse<u64>) {
+        (self.kind, Reverse(self.distance), Reverse(self.order))
+    }
+}
+
+impl PartialEq for Job {
+    fn eq(&self, other: &Self) -> bool {
+        self.key() == other.key()
+    }
+}
+
+impl Eq for Job {}
+
+impl PartialOrd for Job {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for Job {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.key().cmp(&other.key())
+    }
+}
+
+/// Runs a bounded number of chunk jobs on the thread pool, highest priority first. Jobs send
+/// their results back over a channel instead of being polled.
+pub struct ChunkJobs {
+    sender: Mutex<Sender<(IVec2, ChunkTaskData)>>,
+    receiver: Mutex<Receiver<(IVec2, ChunkTaskData)>>,
+    pending: BinaryHeap<Job>,
+    submitted: u64,
+    running: usize,
+    /// Finished meshes waiting for their turn to be uploaded, oldest first
+    uploads: VecDeque<(IVec2, Option<Mesh>)>,
+}
+
+impl Default for ChunkJobs {
+    fn default() -> Self {
+        let (sender, receiver) = channel();
+        ChunkJobs {
+            sender: Mutex::new(sender),
+            receiver: Mutex::new(receiver),
+            pending: BinaryHeap::new(),
+            submitted: 0,
+            running: 0,
+            uploads: VecDeque::new(),
+        }
+    }
+}
+
+impl ChunkJobs {
+    fn submit(&mut self, chunk_id: IVec2, kind: JobKind, centers: &[IVec2]) {
+        let distance = centers
+            .iter()
+            .map(|center| {
+                let offset = chunk_id - *center;
+                offset.x * offset.x + offset.y * offset.y
+            })
+            .min()
+            .unwrap_or(0);
+        self.pending.push(Job {
+            chunk_id,
+            kind,
+            distance,
+            order: self.submitted,
+        });
+        self.submitted += 1;
+    }
+
+    /// Whether there's room for another job without any waiting
+    fn idle(&self, limit: usize) -> bool {
+        self.running + self.pending.len() < limit
+    }
+
+    /// Starts the most important pending jobs until `limit` are running
+    fn start(
+        &mut self,
+        limit: usize,
+        thread_pool: &AsyncComputeTaskPool,
+        world: &Arc<World>,
+        states: &ChunkStates,
+    ) {
+        while self.running < limit {
+            let Job { chunk_id, kind, .. } = match self.pending.pop() {
+                Some(job) => job,
+                None => break,
+            };
+            self.running += 1;
+            let sender = self.sender.lock().unwrap().clone();
+
+            let cancel = states.cancel_token(chunk_id);
+            if cancel.is_cancelled() {
+                // Not worth a trip through the thread pool
+                let _ = sender.send((chunk_id, ChunkTaskData::Cancelled));
+                continue;
+            }
+
+            let world = world.clone();
+            thread_pool
+                .spawn(async move {
+                    let data = match kind {
+                        JobKind::Generate => async_chunk_gen(chunk_id, world, cancel).await,
+                        JobKind::Decorate => async_chunk_decorate(chunk_id, world, cancel).await,
+                        JobKind::Light => async_chunk_light(chunk_id, world, cancel).await,
+                        JobKind::Mesh => async_chunk_mesh(chunk_id, world, cancel).await,
+                    };
+                    // Only fails if the game is shutting down
+                    let _ = sender.send((chunk_id, data));
+                })
+                .detach();
+        }
+    }
+
+    fn receive(&mut self) -> Vec<(IVec2, ChunkTaskData)> {
+        let finished: Vec<_> = self.receiver.lock().unwrap().try_iter().collect();
+        self.running -= finished.len();
+        finished
+    }
+}
+
+enum ChunkTaskData {
+    Generated(Chunk),
+    Decorated,
+    Lit,
+    /// None if there was nothing to draw
+    Meshed(Option<Mesh>),
+    /// The chunk left range before the task got to the end
+    Cancelled,
+}
+
+/// Offsets from the centre chunk of every chunk in range, closest first, for each view distance
+/// an observer is using
+#[derive(Default)]
+pub struct ChunkPriorityMap(HashMap<usize, Vec<IVec2>>);
+
+impl ChunkPriorityMap {
+    /// Drops the offsets for view distances no observer is using any more
+    pub fn retain(&mut self, view_distances: &[usize]) {
+        self.0
+            .retain(|view_distance, _| view_distances.contains(view_distance));
+    }
+
+    pub fn offsets(&mut self, view_distance: usize) -> &[IVec2] {
+        self.0.entry(view_distance).or_insert_with(|| {
+            let radius = view_distance as i32;
+            let mut offsets = Vec::new();
+
+            for x in -(radius - 1)..radius {
+                for y in -(radius - 1)..radius {
+                    let sqr_dist = x * x + y * y;
+                    if sqr_dist < radius * radius {
+                        offsets.push(IVec2::new(x, y));
+                    }
+                }
+            }
+
+            offsets.sort_unstable_by(|a, b| {
+                let a_sqr_dist = a.x * a.x + a.y * a.y;
+                let b_sqr_dist = b.x * b.x + b.y * b.y;
+                a_sqr_dist.cmp(&b_sqr_dist)
+            });
+
+            offsets
+        })
+    }
+}
+
+/// Keeps the chunks around an entity's `Transform` loaded. Everything in range of at least one
+/// observer stays resident, so there can be any number of them.
+#[derive(Default)]
+pub struct ChunkObserver {
+    /// Radius in chunks, or the view distance setting if None. Kept within the same limits as the
+    /// setting.
+    pub view_distance: Option<usize>,
+}
+
+/// An observer as of this frame
+struct ObserverView {
+    center: IVec2,
+    view_distance: usize,
+    /// Horizontal direction it's facing, zero when looking straight up or down
+    forward: Vec2,
+    /// Horizontal direction it's moving in, zero if it's not a character or barely moving
+    travel: Vec2,
+}
+
+fn observer_views(
+    observers: &Query<(&Transform, &ChunkObserver, Option<&Character>)>,
+    settings: &Settings,
+) -> Vec<ObserverView> {
+    let horizontal = |direction: Vec3, min_length: f32| {
+        let direction = Vec2::new(direction.x, direction.z);
+        if direction.length_squared() > min_length * min_length {
+            direction.normalize()
+        } else {
+            Vec2::ZERO
+        }
+    };
+
+    observers
+        .iter()
+        .map(|(transform, observer, character)| ObserverView {
+            center: ChunkPos::from_world(transform.translation).0,
+            view_distance: observer
+                .view_distance
+                .unwrap_or(settings.view_distance)
+                .clamp(MIN_VIEW_DISTANCE, MAX_VIEW_DISTANCE),
+            forward: horizontal(transform.rotation * -Vec3::Z, 0.001),
+            travel: character.map_or(Vec2::ZERO, |character| horizontal(character.velocity, 1.0)),
+        })
+        .collect()
+}
+
+/// Queues chunks that came into range of an observer and unloads the ones that went past the
+/// unload radius of every observer, whenever an observer crosses into a new chunk or its view
+/// distance changes, and every frame while chunks back in range are still unloading
+pub fn update_chunk_state(
+    mut commands: Commands,
+    observers: Query<(&Transform, &ChunkObserver, Option<&Character>)>,
+    mut last_areas: Local<Vec<(IVec2, usize)>>,
+    world: Res<Arc<World>>,
+    mut states: ResMut<ChunkStates>,
+    mut cache: ResMut<ChunkCache>,
+    mut chunk_priority_map: ResMut<ChunkPriorityMap>,
+    settings: Res<Settings>,
+) {
+    cache.set_budget(settings.chunk_cache_mb.saturating_mul(1024 * 1024));
+
+    let areas: Vec<(IVec2, usize)> = observer_views(&observers, &settings)
+        .iter()
+        .map(|view| (view.center, view.view_distance))
+        .collect();
+    if *last_areas == areas {
+        return;
+    }
+    *last_areas = areas.clone();
+    let view_distances: Vec<usize> = areas
+        .iter()
+        .map(|(_, view_distance)| *view_distance)
+        .collect();
+    chunk_priority_map.retain(&view_distances);
+
+    let out_of_range: Vec<IVec2> = states
+        .ids()
+        .filter(|id| {
+            areas.iter().all(|(center, view_distance)| {
+                let offset = *id - *center;
+                let unload_radius = *view_distance as i32 + UNLOAD_MARGIN;
+                offset.x * offset.x + offset.y * offset.y >= unload_radius * unload_radius
+            })
+        })
+        .collect();
+    for chunk_id in out_of_range {
+        unload_chunk(&mut commands, &mut states, &mut cache, &world, chunk_id);
+    }
+
+    // Chunks still unloading can't be queued until their job has reported back, so this runs
+    // again every frame until they're gone
+    let mut unloading = false;
+    for (center, view_distance) in areas {
+        for local_offset in chunk_priority_map.offsets(view_distance) {
+            let chunk_id = *local_offset + center;
+            match states.status(chunk_id) {
+                None => states.queue(chunk_id),
+                Some(ChunkStatus::Unloading) => unloading = true,
+                Some(_) => {}
+            }
+        }
+    }
+    if unloading {
+        last_areas.clear();
+    }
+}
+
+fn unload_chunk(
+    commands: &mut Commands,
+    states: &mut ChunkStates,
+    cache: &mut ChunkCache,
+    world: &World,
+    chunk_id: IVec2,
+) {
+    let state = match states.get_mut(chunk_id) {
+        Some(state) if state.status != ChunkStatus::Unloading => state,
+        _ => return,
+    };
+
+    if let Some(mesh) = state.mesh.take() {
+        commands.entity(mesh).despawn();
+    }
+
+    if state.in_flight() {
+        // Removed once the task reports back, so it never works on a chunk that's gone
+        state.cancel.cancel();
+        states.transition(chunk_id, ChunkStatus::Unloading);
+    } else if state.status.reached(ChunkStatus::Decorated) {
+        cache_chunk(states, cache, world, chunk_id);
+    } else {
+        remove_chunk(states, world, chunk_id);
+    }
+
+    // Meshes along the new edge can't be drawn without this chunk
+    for dir in &neighbors[1..] {
+        let neighbor_id = chunk_id + IVec2::from(*dir);
+        if states.status(neighbor_id) == Some(ChunkStatus::Ready) {
+            let state = states.get_mut(neighbor_id).unwrap();
+            if let Some(mesh) = state.mesh.take() {
+                commands.entity(mesh).despawn();
+            }
+            states.transition(neighbor_id, ChunkStatus::Lit);
+        }
+    }
+}
+
+fn remove_chunk(states: &mut ChunkStates, world: &World, chunk_id: IVec2) {
+    states.remove(chunk_id);
+    world.chunks.remove(&chunk_id);
+    decoration::take_spill(world, chunk_id);
+}
+
+fn cache_chunk(states: &mut ChunkStates, cache: &mut ChunkCache, world: &World, chunk_id: IVec2) {
+    states.remove(chunk_id);
+    let spill = decoration::take_spill(world, chunk_id);
+    if let Some((_, chunk)) = world.chunks.remove(&chunk_id) {
+        cache.insert(chunk_id, CachedChunk { chunk, spill });
+    }
+}
+
+/// Puts a cached chunk back as `Decorated`, lit again from scratch as its neighbours may have
+/// changed while it was gone
+fn restore_chunk(states: &mut ChunkStates, world: &World, chunk_id: IVec2, cached: CachedChunk) {
+    let CachedChunk { mut chunk, spill } = cached;
+    chunk.clear_light();
+    world.chunks.insert(chunk_id, chunk);
+    decoration::apply_spill(world, chunk_id);
+    decoration::restore_spill(world, chunk_id, spill, |target| {
+        states
+            .status(target)
+            .map_or(false, |status| status.reached(ChunkStatus::Lit))
+    });
+    states.transition(chunk_id, ChunkStatus::Decorated);
+}
+
+/// Orders queued chunks by distance to the closest observer, with the ones in its view and in its
+/// direction of travel first. Sorted again when more chunks are queued or an observer turns.
+pub fn prioritise_chunks(
+    observers: Query<(&Transform, &ChunkObserver, Option<&Character>)>,
+    mut last: Local<Vec<(IVec2, usize, Vec2)>>,
+    windows: Option<Res<Windows>>,
+    settings: Res<Settings>,
+    mut states: ResMut<ChunkStates>,
+) {
+    let views = observer_views(&observers, &settings);
+    let unchanged = last.len() == views.len()
+        && last
+            .iter()
+            .zip(&views)
+            .all(|((center, view_distance, forward), view)| {
+                *center == view.center
+                && *view_distance == view.view_distance
+                // Close enough to the angle between them, and fine with zero vectors
+                && (*forward - view.forward).length() < REPRIORITISE_ANGLE
+            });
+    if unchanged {
+        return;
+    }
+    *last = views
+        .iter()
+        .map(|view| (view.center, view.view_distance, view.forward))
+        .collect();
+
+    let aspect = windows
+        .as_ref()
+        .and_then(|windows| windows.get_primary())
+        .map_or(1.0, |window| window.width() / window.height());
+    let half_fov = ((settings.fov.to_radians() / 2.0).tan() * aspect).atan() + VIEW_MARGIN;
+
+    states.prioritise_queue(|chunk_id| {
+        views
+            .iter()
+            .map(|view| {
+                let offset = chunk_id - view.center;
+                let offset = Vec2::new(offset.x as f32, offset.y as f32);
+                let distance = offset.length();
+                if distance <= NEAR_RADIUS {
+                    return distance;
+                }
+
+                let direction = offset / distance;
+                let mut weight = 1.0;
+                if view.forward.dot(direction) >= half_fov.cos() {
+                    weight -= VIEW_WEIGHT;
+                }
+                weight -= TRAVEL_WEIGHT * view.travel.dot(direction).max(0.0);
+                distance * weight
+            })
+            .fold(f32::INFINITY, f32::min)
+    });
+}
+
+/// Collects finished jobs and submits jobs for every chunk that is able to move on
+pub fn handle_chunk_tasks(
+    observers: Query<&Transform, With<ChunkObserver>>,
+    thread_pool: Res<AsyncComputeTaskPool>,
+    world: Res<Arc<World>>,
+    mut states: ResMut<ChunkStates>,
+    mut cache: ResMut<ChunkCache>,
+    mut jobs: ResMut<ChunkJobs>,
+) {
+    for (chunk_id, chunk_task_data) in jobs.receive() {
+        let cancelled = matches!(chunk_task_data, ChunkTaskData::Cancelled);
+        if discard_unloading(&mut states, &world, chunk_id, cancelled) {
+            continue;
+        }
+
+        match chunk_task_data {
+            ChunkTaskData::Generated(chunk) => {
+                world.chunks.insert(chunk_id, chunk);
+                decoration::apply_spill(&world, chunk_id);
+                states.transition(chunk_id, ChunkStatus::Terrain);
+            }
+            ChunkTaskData::Decorated => {
+                states.get_mut(chunk_id).unwrap().busy = false;
+                states.transition(chunk_id, ChunkStatus::Decorated);
+            }
+            ChunkTaskData::Lit => {
+                states.get_mut(chunk_id).unwrap().busy = false;
+                states.transition(chunk_id, ChunkStatus::Lit);
+            }
+            // Stays `Meshing` until it's uploaded
+            ChunkTaskData::Meshed(mesh) => jobs.uploads.push_back((chunk_id, mesh)),
+            ChunkTaskData::Cancelled => {
+                unreachable!("Only tasks for unloading chunks are cancelled")
+            }
+        }
+    }
+
+    let centers: Vec<IVec2> = observers
+        .iter()
+        .map(|transform| ChunkPos::from_world(transform.translation).0)
+        .collect();
+
+    // Edited chunks that are still meshing stay queued until the mesh in flight has landed
+    let edited: Vec<IVec2> = world.remesh.iter().map(|chunk_id| *chunk_id).collect();
+    for chunk_id in edited {
+        match states.status(chunk_id) {
+            Some(ChunkStatus::Meshing) => continue,
+            Some(ChunkStatus::Ready) => {
+                states.transition(chunk_id, ChunkStatus::Meshing);
+                jobs.submit(chunk_id, JobKind::Mesh, &centers);
+            }
+            _ => {}
+        }
+        world.remesh.remove(&chunk_id);
+    }
+
+    for chunk_id in states.take_dirty() {
+        let (status, busy) = match states.get(chunk_id) {
+            Some(state) => (state.status, state.busy),
+            None => continue,
+        };
+        if busy {
+            continue;
+        }
+
+        match status {
+            ChunkStatus::Terrain if states.neighbors_reached(chunk_id, ChunkStatus::Terrain) => {
+                states.get_mut(chunk_id).unwrap().busy = true;
+                jobs.submit(chunk_id, JobKind::Decorate, &centers);
+            }
+            ChunkStatus::Decorated
+                if states.neighbors_reached(chunk_id, ChunkStatus::Decorated) =>
+            {
+                states.get_mut(chunk_id).unwrap().busy = true;
+                jobs.submit(chunk_id, JobKind::Light, &centers);
+            }
+            ChunkStatus::Lit if states.neighbors_reached(chunk_id, ChunkStatus::Lit) => {
+                states.transition(chunk_id, ChunkStatus::Meshing);
+                jobs.submit(chunk_id, JobKind::Mesh, &centers);
+            }
+            _ => {}
+        }
+    }
+
+    // New chunks only take up slots nothing else wants, so they don't hold up chunks that are
+    // nearly ready, and stay in the queue where they can still be reordered
+    let limit = thread_pool.thread_num() * JOBS_PER_THREAD;
+    while jobs.idle(limit) {
+        let chunk_id = match states.pop_queued() {
+            Some(chunk_id) => chunk_id,
+            None => break,
+        };
+        if states.status(chunk_id) != Some(ChunkStatus::Queued) {
+            continue;
+        }
+        if let Some(cached) = cache.take(chunk_id) {
+            restore_chunk(&mut states, &world, chunk_id, cached);
+        } else {
+            states.transition(chunk_id, ChunkStatus::Generating);
+            jobs.submit(chunk_id, JobKind::Generate, &centers);
+        }
+    }
+
+    jobs.start(limit, &thread_pool, &world, &states);
+}
+
+/// Uploads as many finished meshes as the budget allows. Without a renderer they're dropped and
+/// the chunks are marked ready straight away.
+pub fn upload_chunk_meshes(
+    mut commands: Commands,
+    meshes: Option<ResMut<Assets<Mesh>>>,
+    material_handle: Option<Res<ChunkMaterialHandle>>,
+    pipeline_handle: Option<Res<ChunkPipelineHandle>>,
+    world: Res<Arc<World>>,
+    mut states: ResMut<ChunkStates>,
+    mut jobs: ResMut<ChunkJobs>,
+    settings: Res<Settings>,
+) {
+    let mut renderer = match (meshes, material_handle, pipeline_handle) {
+        (Some(meshes), Some(material_handle), Some(pipeline_handle)) => {
+            Some((meshes, material_handle, pipeline_handle))
+        }
+        _ => None,
+    };
+
+    let mut uploaded = 0;
+    while uploaded < settings.mesh_uploads_per_frame {
+        let (chunk_id, mesh) = match jobs.uploads.pop_front() {
+            Some(upload) => upload,
+            None => break,
+        };
+        if discard_unloading(&mut states, &world, chunk_id, false) {
+            continue;
+        }
+
+        // A neighbour left while meshing, so the borders are wrong
+        if !states.neighbors_reached(chunk_id, ChunkStatus::Lit) {
+            WASTED_TASKS.fetch_add(1, Ordering::Relaxed);
+            states.transition(chunk_id, ChunkStatus::Lit);
+            continue;
+        }
+
+        let (meshes, material_handle, pipeline_handle) = match &mut renderer {
+            Some(renderer) => renderer,
+            None => {
+                states.transition(chunk_id, ChunkStatus::Ready);
+                continue;
+            }
+        };
+        let state = states.get_mut(chunk_id).unwrap();
+        match (mesh, state.mesh) {
+            (Some(mesh), Some(entity)) => {
+                commands.entity(entity).insert(meshes.add(mesh));
+                uploaded += 1;
+            }
+            (Some(mesh), None) => {
+                let entity = commands
+                    .spawn_bundle(MeshBundle {
+                        mesh: meshes.add(mesh),
+                        render_pipelines: RenderPipelines::from_pipelines(vec![
+                            RenderPipeline::new(pipeline_handle.0.clone()),
+                        ]),
+                        transform: Transform::from_translation(ChunkPos(chunk_id).translation()),
+                        ..Default::default()
+                    })
+                    .insert(material_handle.0.clone())
+                    .insert(ChunkComponent { chunk_id })
+                    .id();
+                state.mesh = Some(entity);
+                uploaded += 1;
+            }
+            (None, Some(entity)) => {
+                commands.entity(entity).despawn();
+                state.mesh = None;
+            }
+            (None, None) => {}
+        }
+        states.transition(chunk_id, ChunkStatus::Ready);
+    }
+}
+
+/// Removes the chunk if it was unloaded while its job was running. Returns whether the job's
+/// result should be thrown away.
+fn discard_unloading(
+    states: &mut ChunkStates,
+    world: &World,
+    chunk_id: IVec2,
+    cancelled: bool,
+) -> bool {
+    match states.status(chunk_id) {
+        Some(ChunkStatus::Unloading) => {}
+        Some(_) => return false,
+        None => return true,
+    }
+
+    let counter = if cancelled {
+        &CANCELLED_TASKS
+    } else {
+        &WASTED_TASKS
+    };
+    counter.fetch_add(1, Ordering::Relaxed);
+    remove_chunk(states, world, chunk_id);
+    true
+}
+
+async fn async_chunk_gen(chunk_id: IVec2, world: Arc<World>, cancel: CancelToken) -> ChunkTaskData {
+    if cancel.is_cancelled() {
+        return ChunkTaskData::Cancelled;
+    }
+    COUNTER.fetch_add(1, Ordering::Relaxed);
+
+    ChunkTaskData::Generated(world.generate(chunk_id))
+}
+
+/// Decoration and lighting are only cancelled before they start, as both write into neighbours
+/// that may still be loaded
+async fn async_chunk_decorate(
+    chunk_id: IVec2,
+    world: Arc<World>,
+    cancel: CancelToken,
+) -> ChunkTaskData {
+    if cancel.is_cancelled() {
+        return ChunkTaskData::Cancelled;
+    }
+    COUNTER3.fetch_add(1, Ordering::Relaxed);
+
+    if world.preset.decorates() {
+        decoration::decorate(&world, chunk_id);
+    }
+
+    ChunkTaskData::Decorated
+}
+
+async fn async_chunk_light(
+    chunk_id: IVec2,
+    world: Arc<World>,
+    cancel: CancelToken,
+) -> ChunkTaskData {
+    if cancel.is_cancelled() {
+        return ChunkTaskData::Cancelled;
+    }
+    light::light_chunk(&world, chunk_id);
+
+    ChunkTaskData::Lit
+}
+
+async fn async_chunk_mesh(
+    chunk_id: IVec2,
+    world: Arc<World>,
+    cancel: CancelToken,
+) -> ChunkTaskData {
+    if cancel.is_cancelled() {
+        return ChunkTaskData::Cancelled;
+    }
+    COUNTER2.fetch_add(1, Ordering::Relaxed);
+
+    let chunk = world
+        .chunks
+        .get(&chunk_id)
+        .expect("Tried to mesh a chunk that wasn't generated");
+    let tmp_mesh = chunk.generate_mesh(&world);
+    drop(chunk);
+    // Building the mesh copies every attribute, which is pointless for a chunk that's gone
+    if cancel.is_cancelled() {
+        return ChunkTaskData::Cancelled;
+    }
+
+    ChunkTaskData::Meshed(tmp_mesh.into_mesh())
+}
@@ -0,0 +1,137 @@
+use bevy::prelude::{IVec2, IVec3, Vec3};
+use dashmap::{DashMap, DashSet};
+use std::sync::Mutex;
+
+use super::chunk::*;
+use super::coords::{BlockPos, ChunkPos};
+use super::decoration;
+use super::erosion::Erosion;
+use super::light;
+use super::neighbors;
+use super::preset::WorldPreset;
+
+/// Block data shared with the generation and meshing tasks. Where each chunk is in its lifecycle is
+/// tracked on the main thread by `ChunkStates`.
+pub struct World {
+    pub chunks: DashMap<IVec2, Chunk>,
+    /// Blocks that features placed outside the chunk they're rooted in, keyed by the chunk they
+    /// landed in and tagged with the chunk that placed them
+    pub spill: DashMap<IVec2, Vec<(IVec2, IVec3, u16)>>,
+    pub preset: WorldPreset,
+    pub seed: i32,
+    /// Only set when erosion is turned on
+    pub erosion: Option<Erosion>,
+    /// Chunks whose blocks or light were edited and need their mesh rebuilt
+    pub remesh: DashSet<IVec2>,
+    /// Where characters start, at their feet. Found once the chunks around the origin exist.
+    spawn: Mutex<Option<Vec3>>,
+}
+
+impl World {
+    pub fn new(preset: WorldPreset, seed: i32, erosion: bool) -> Self {
+        World {
+            chunks: DashMap::new(),
+            spill: DashMap::new(),
+            preset,
+            seed,
+            erosion: if erosion {
+                Some(Erosion::default())
+            } else {
+                None
+            },
+            remesh: DashSet::new(),
+            spawn: Mutex::new(None),
+        }
+    }
+
+    pub fn spawn(&self) -> Option<Vec3> {
+        *self.spawn.lock().unwrap()
+    }
+
+    pub fn set_spawn(&self, spawn: Vec3) {
+        *self.spawn.lock().unwrap() = Some(spawn);
+    }
+
+    /// Terrain for a new chunk, before decoration
+    pub fn generate(&self, chunk_id: IVec2) -> Chunk {
+        let pos = ChunkPos(chunk_id).origin().0;
+
+        let mut chunk = Chunk::new(chunk_id);
+        self.preset.generate(&mut chunk, pos, self.seed);
+        if let (Some(erosion), Some(amplitude)) = (&self.erosion, self.preset.amplitude()) {
+            erosion.apply(&mut chunk, pos, amplitude, self.seed);
+        }
+        chunk
+    }
+
+    /// Generates, decorates and lights every chunk up to `radius` chunks from `center` in one go,
+    /// for tools, tests and benchmarks that want chunks ready to mesh without streaming them. Only
+    /// chunks at least one in from the edge have all the neighbours meshing needs.
+    pub fn load_square(&self, center: IVec2, radius: i32) {
+        let square: Vec<IVec2> = (-radius..=radius)
+            .flat_map(|x| (-radius..=radius).map(move |z| center + IVec2::new(x, z)))
+            .collect();
+
+        for chunk_id in &square {
+            self.chunks.insert(*chunk_id, self.generate(*chunk_id));
+            decoration::apply_spill(self, *chunk_id);
+        }
+        if self.preset.decorates() {
+            for chunk_id in &square {
+                decoration::decorate(self, *chunk_id);
+            }
+        }
+        for chunk_id in &square {
+            light::light_chunk(self, *chunk_id);
+        }
+    }
+
+    pub fn get_block(&self, pos: IVec3) -> Option<u16> {
+        if pos.y < 0 || pos.y >= CHUNK_SIZE_Y as i32 {
+            return None;
+        }
+
+        let (chunk_id, local) = BlockPos(pos).split();
+        let (x, y, z) = local.index();
+        self.chunks
+            .get(&chunk_id.0)
+            .map(|chunk| chunk.values[x][y][z])
+    }
+
+    /// Changes a block after its chunk has been lit, relighting around it and queueing every mesh
+    /// that could see the change. Returns false if the position isn't in a loaded chunk.
+    pub fn set_block(&self, pos: IVec3, block: u16) -> bool {
+        if pos.y < 0 || pos.y >= CHUNK_SIZE_Y as i32 {
+            return false;
+        }
+
+        let (ChunkPos(chunk_id), local) = BlockPos(pos).split();
+        let (x, y, z) = local.index();
+        match self.chunks.get_mut(&chunk_id) {
+            Some(mut chunk) => chunk.set_block(x, y, z, block),
+            None => return false,
+        }
+
+        let mut touched = light::block_changed(self, pos);
+        touched.insert(chunk_id);
+        // Faces along a chunk's edge are lit from its neighbours
+        for touched_id in touched {
+            for dir in neighbors {
+                self.remesh.insert(touched_id + IVec2::from(dir));
+            }
+        }
+        true
+    }
+
+    /// World y of the highest block that stops movement in the column, or None if the chunk isn't
+    /// loaded or the column is empty
+    pub fn surface_height(&self, x: i32, z: i32) -> Option<i32> {
+        let (chunk_id, local) = BlockPos(IVec3::new(x, 0, z)).split();
+        let (x, _, z) = local.index();
+        self.chunks.get(&chunk_id.0).and_then(|chunk| {
+            chunk
+                .height(Heightmap::MotionBlocking, x, z)
+                .map(|y| y as i32)
+        })
+    }
+}
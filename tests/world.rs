@@ -0,0 +1,47 @@
+use bevy::prelude::*;
+use steve::{
+    block::{AIR, DIRT},
+    chunk::{Heightmap, CHUNK_SIZE_X, CHUNK_SIZE_Y, CHUNK_SIZE_Z},
+    light::{LightChannel, MAX_LIGHT},
+    preset::WorldPreset,
+    world::World,
+};
+
+fn loaded_world(preset: WorldPreset) -> World {
+    let world = World::new(preset, 1, false);
+    world.load_square(IVec2::ZERO, 1);
+    world
+}
+
+#[test]
+fn default_terrain() {
+    let world = loaded_world(WorldPreset::Default);
+    let chunk = world.chunks.get(&IVec2::ZERO).unwrap();
+
+    for x in 0..CHUNK_SIZE_X {
+        for z in 0..CHUNK_SIZE_Z {
+            assert!(chunk.height(Heightmap::MotionBlocking, x, z).is_some());
+        }
+    }
+    assert_eq!(
+        chunk.light(LightChannel::Sky, 0, CHUNK_SIZE_Y - 1, 0),
+        MAX_LIGHT
+    );
+    assert!(chunk.generate_mesh(&world).into_mesh().is_some());
+}
+
+#[test]
+fn superflat_terrain() {
+    let world = loaded_world(WorldPreset::parse("superflat", Some("2*stone,dirt")).unwrap());
+
+    // Every chunk around the origin, including the ones at negative coordinates
+    for (x, z) in [(-20, -20), (0, 0), (-1, 40), (40, -1)] {
+        assert_eq!(world.get_block(IVec3::new(x, 2, z)), Some(DIRT));
+        assert_eq!(world.get_block(IVec3::new(x, 3, z)), Some(AIR));
+        assert_eq!(world.surface_height(x, z), Some(2));
+    }
+
+    let chunk = world.chunks.get(&IVec2::new(-1, -1)).unwrap();
+    assert_eq!(chunk.light(LightChannel::Sky, 0, 3, 0), MAX_LIGHT);
+    assert!(chunk.generate_mesh(&world).into_mesh().is_some());
+}